@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Number of simulation ticks per simulated second.
+pub const TICKS_PER_SECOND: u64 = 60;
+
+/// Fixed-timestep simulation clock owned by the `World`.
+///
+/// Time only moves when the world is stepped, so the same inputs always produce the same
+/// timings no matter how fast (or slow) the host runs the loop.
+#[derive(Clone, Copy, Debug)]
+pub struct SimClock {
+    tick: u64,
+    dt: f64,
+}
+
+impl SimClock {
+    /// Creates a clock at tick 0 that advances by `dt` seconds per tick.
+    pub fn new(dt: f64) -> Self {
+        SimClock { tick: 0, dt }
+    }
+
+    /// Advances the clock by one tick.
+    pub fn advance(&mut self) {
+        self.tick += 1;
+    }
+
+    /// Number of ticks elapsed since the start of the simulation.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Length of one tick in seconds.
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Simulated time elapsed since the start of the simulation.
+    pub fn now(&self) -> Duration {
+        Duration::from_secs_f64(self.tick as f64 * self.dt)
+    }
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        SimClock::new(1.0 / TICKS_PER_SECOND as f64)
+    }
+}
@@ -0,0 +1,581 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::clock::SimClock;
+use crate::road::{Approach, ConflictBox, IntersectionGeometry, JunctionKind, CROSSWALK_WIDTH, STOP_LINE_WIDTH};
+use crate::traffic_light::{PhasePlan, SignalController};
+use crate::vehicle::Vehicle;
+use crate::view::View;
+
+/// Maximum number of vehicles allowed in the intersection before arrivals are held at the stop line.
+const MAX_VEHICLES_IN_INTERSECTION: usize = 3;
+
+/// What a controller tells a vehicle to do this tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// The vehicle may drive at its cruise speed (it still has to avoid the vehicles around it).
+    Proceed,
+    /// The vehicle must come to a stop within the given distance.
+    StopWithin(f64),
+}
+
+impl Command {
+    /// The stricter of the two commands: the shorter stop, or `Proceed` if neither stops.
+    pub fn and(self, other: Command) -> Command {
+        match (self, other) {
+            (Command::StopWithin(a), Command::StopWithin(b)) => Command::StopWithin(a.min(b)),
+            (Command::StopWithin(d), Command::Proceed) | (Command::Proceed, Command::StopWithin(d)) => Command::StopWithin(d),
+            (Command::Proceed, Command::Proceed) => Command::Proceed,
+        }
+    }
+}
+
+/// Intersection policy: decides each tick which vehicles may enter the intersection or must hold.
+pub trait IntersectionController {
+    /// Short name shown in the stats.
+    fn name(&self) -> &'static str;
+
+    /// Returns one command per vehicle, in the same order as `vehicles`.
+    fn decide(&mut self, vehicles: &[&Vehicle], clock: &SimClock) -> Vec<Command>;
+
+    /// Checks if pedestrians may step onto the crosswalk across the `side` leg. Controllers without
+    /// pedestrian signals let them go whenever the vehicles can stop for them.
+    fn may_cross(&self, _side: Approach) -> bool {
+        true
+    }
+
+    /// Draws controller state (e.g. signal lights) on top of the roads, placed on the window by `view`.
+    fn draw(&self, _canvas: &mut Canvas<Window>, _view: &View) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Builds the controller registered under `name`, as used by `--controller`.
+///
+/// `signal_plan` is the path of the phase plan used by the `signal` controller. Roundabouts always get
+/// a `YieldController`, as their priority rule is part of the junction.
+pub fn from_name(
+    name: &str,
+    signal_plan: Option<&str>,
+    geometry: &IntersectionGeometry,
+) -> Result<Box<dyn IntersectionController>, String> {
+    if geometry.kind == JunctionKind::Roundabout {
+        return Ok(Box::new(YieldController::new(geometry)));
+    }
+    match name {
+        "fcfs" => Ok(Box::new(FcfsController)),
+        "reservation" => Ok(Box::new(ReservationController::new(geometry))),
+        "signal" => {
+            let plan = match signal_plan {
+                Some(path) => PhasePlan::from_file(path)?,
+                None => PhasePlan::default(),
+            };
+            Ok(Box::new(SignalController::new(plan, geometry)?))
+        }
+        _ => Err(format!("Unknown controller: {} (expected fcfs, reservation or signal)", name)),
+    }
+}
+
+/// First-come-first-served: a vehicle approaching the intersection yields to anyone who entered
+/// before it, and arrivals wait at the stop line while the intersection is full. A vehicle counts as
+/// in the intersection for as long as any part of it is.
+pub struct FcfsController;
+
+impl IntersectionController for FcfsController {
+    fn name(&self) -> &'static str {
+        "fcfs"
+    }
+
+    fn decide(&mut self, vehicles: &[&Vehicle], clock: &SimClock) -> Vec<Command> {
+        let vehicles_in_intersection = vehicles.iter()
+            .filter(|v| v.occupies_intersection())
+            .count();
+
+        vehicles.iter()
+            .map(|vehicle| {
+                // Vehicles already partly in the intersection are among those filling it.
+                let intersection_full = vehicles_in_intersection >= MAX_VEHICLES_IN_INTERSECTION
+                    && Self::is_at_stop_line(vehicle)
+                    && !vehicle.occupies_intersection();
+                if intersection_full || !Self::has_priority(vehicle, vehicles, clock.now()) {
+                    Command::StopWithin(vehicle.distance_to_stop_line())
+                } else {
+                    Command::Proceed
+                }
+            })
+            .collect()
+    }
+}
+
+impl FcfsController {
+    /// Determines if the given vehicle has priority to move into the intersection.
+    ///
+    /// A vehicle approaching the intersection (using a 5 m buffer) must yield to any vehicle that
+    /// has already entered. Once in the intersection, the vehicle's recorded entry time determines its
+    /// priority. `now` is the current simulation time.
+    fn has_priority(vehicle: &Vehicle, vehicles: &[&Vehicle], now: Duration) -> bool {
+        // The vehicle that is not yet near the intersection can continue normally.
+        if !vehicle.is_approaching_intersection() {
+            return true;
+        }
+
+        // If the vehicle is already in the intersection, it has priority.
+        if vehicle.occupies_intersection() {
+            return true;
+        }
+
+        // Determine this vehicle's "entry time" (if it hasn't been set, use the current time).
+        let self_time = vehicle.intersection_entry_time.unwrap_or(now);
+
+        // If any other vehicle in the intersection has an earlier entry time, then this vehicle must wait.
+        for other in vehicles {
+            if vehicle.id == other.id {
+                continue;
+            }
+            if other.occupies_intersection() {
+                if let Some(other_time) = other.intersection_entry_time {
+                    if other_time < self_time {
+                        return false;
+                    }
+                } else {
+                    // If the other vehicle is in the intersection but its time is not set,
+                    // assume it came in first.
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Checks if the vehicle is between its stop line and the edge of the intersection.
+    fn is_at_stop_line(vehicle: &Vehicle) -> bool {
+        vehicle.distance_to_stop_line() <= 0.0 && vehicle.distance_to_intersection() >= 0.0
+    }
+}
+
+/// Distance in meters from the yield line at which vehicles entering a roundabout start looking for a gap.
+const YIELD_DISTANCE: f64 = 10.0;
+/// Seconds of free ring a vehicle needs ahead of the circulating traffic to merge.
+const CRITICAL_GAP: f64 = 2.5;
+
+/// Yield-at-entry rule of a roundabout: the vehicles on the ring have priority.
+///
+/// A vehicle nearing the yield line may merge once no vehicle on the ring, or driving onto it from
+/// another approach, will reach its merge point within the critical gap; otherwise it waits at the
+/// line. A vehicle that found a gap keeps it once it can no longer stop comfortably before the line.
+pub struct YieldController {
+    /// Where each approach merges into the ring, indexed like `Approach::ALL`.
+    merge_points: [(f64, f64); 4],
+    /// Vehicles that were allowed to merge on the last tick.
+    merging: HashSet<u32>,
+}
+
+impl YieldController {
+    pub fn new(geometry: &IntersectionGeometry) -> Self {
+        YieldController {
+            merge_points: Approach::ALL.map(|approach| geometry.merge_point(approach, 0)),
+            merging: HashSet::new(),
+        }
+    }
+
+    /// Checks if `vehicle` is at `point` or will drive over it within the critical gap at its cruise
+    /// speed around the ring.
+    fn reaches(vehicle: &Vehicle, point: (f64, f64)) -> bool {
+        let lookahead = CRITICAL_GAP * vehicle.junction_speed();
+        std::iter::once((vehicle.x, vehicle.y))
+            .chain(vehicle.predicted_path(PATH_STEP, lookahead))
+            .any(|(x, y)| (x - point.0).hypot(y - point.1) < vehicle.length())
+    }
+}
+
+impl IntersectionController for YieldController {
+    fn name(&self) -> &'static str {
+        "yield"
+    }
+
+    fn decide(&mut self, vehicles: &[&Vehicle], _clock: &SimClock) -> Vec<Command> {
+        let circulating: Vec<&Vehicle> = vehicles.iter().copied().filter(|v| v.occupies_intersection()).collect();
+        let mut merging = HashSet::new();
+        let commands = vehicles.iter()
+            .map(|vehicle| {
+                let distance = vehicle.distance_to_stop_line();
+                if vehicle.is_in_intersection() || vehicle.is_leaving_intersection() || distance > YIELD_DISTANCE {
+                    return Command::Proceed;
+                }
+                let committed = self.merging.contains(&vehicle.id) && vehicle.braking_distance() >= distance;
+                let approach = vehicle.junction_approach();
+                let merge_point = self.merge_points[approach.index()];
+                // Vehicles from the same approach are ahead in the queue, not in the way.
+                let blocked = circulating.iter()
+                    .any(|other| other.junction_approach() != approach && Self::reaches(other, merge_point));
+                if committed || !blocked {
+                    merging.insert(vehicle.id);
+                    Command::Proceed
+                } else {
+                    Command::StopWithin(distance)
+                }
+            })
+            .collect();
+        self.merging = merging;
+        commands
+    }
+}
+
+/// Number of tiles the intersection box is split into along each axis.
+const TILE_GRID_SIZE: usize = 8;
+/// Distance in meters added to a vehicle's stopping distance to get where it asks for a reservation,
+/// covering the tick it is refused in.
+const REQUEST_MARGIN: f64 = 1.0;
+/// Spacing in meters of the points sampled along a vehicle's predicted path.
+const PATH_STEP: f64 = 0.4;
+/// Extra seconds added on both sides of every tile reservation.
+const RESERVATION_MARGIN: f64 = 0.3;
+
+/// A tile of the intersection box held by one vehicle between two simulation times, in seconds.
+#[derive(Clone, Copy)]
+struct TileReservation {
+    vehicle_id: u32,
+    from_time: f64,
+    to_time: f64,
+}
+
+/// Reservation-based manager in the style of AIM (Autonomous Intersection Management).
+///
+/// The intersection box is split into a grid of tiles. Each vehicle near the box asks for the tiles
+/// its route sweeps over the next seconds; it may enter once every tile is free for the time it needs,
+/// otherwise it is told to stop at its stop line and ask again on the next tick. Vehicles whose paths
+/// do not share tiles at the same time cross simultaneously.
+pub struct ReservationController {
+    conflict_box: ConflictBox,
+    tiles: HashMap<(usize, usize), Vec<TileReservation>>,
+    /// Vehicles holding a reservation, which is refreshed from their position every tick.
+    granted: HashSet<u32>,
+}
+
+impl ReservationController {
+    pub fn new(geometry: &IntersectionGeometry) -> Self {
+        ReservationController {
+            conflict_box: geometry.conflict_box(),
+            tiles: HashMap::new(),
+            granted: HashSet::new(),
+        }
+    }
+
+    /// Tiles covered by a square of side `2 * half_size` centred on the given point.
+    fn tiles_at(&self, x: f64, y: f64, half_size: f64) -> Vec<(usize, usize)> {
+        let tile_width = self.conflict_box.width() / TILE_GRID_SIZE as f64;
+        let tile_height = self.conflict_box.height() / TILE_GRID_SIZE as f64;
+
+        let column = |x: f64| ((x - self.conflict_box.left) / tile_width).floor() as i64;
+        let row = |y: f64| ((y - self.conflict_box.top) / tile_height).floor() as i64;
+        let last = TILE_GRID_SIZE as i64 - 1;
+        let (first_column, last_column) = (column(x - half_size).max(0), column(x + half_size).min(last));
+        let (first_row, last_row) = (row(y - half_size).max(0), row(y + half_size).min(last));
+
+        let mut tiles = Vec::new();
+        for column in first_column..=last_column {
+            for row in first_row..=last_row {
+                tiles.push((column as usize, row as usize));
+            }
+        }
+        tiles
+    }
+
+    /// Tiles the vehicle's route sweeps through, each with the window of simulation time it may occupy it.
+    ///
+    /// The footprint is swept along the route, so a point of the route is covered from when the
+    /// vehicle's front reaches it until its back has passed; the route is sampled from under the back
+    /// of the vehicle onwards. The window runs from the earliest arrival (accelerating hard to top
+    /// speed) to the latest departure (accelerating gently to half the intersection speed), so small
+    /// delays stay inside it.
+    fn plan(&self, vehicle: &Vehicle, now: f64) -> HashMap<(usize, usize), (f64, f64)> {
+        let half_width = vehicle.width() / 2.0;
+        // How far the body reaches beyond its center, past the half width covered around each point.
+        let reach = (vehicle.length() - vehicle.width()).max(0.0) / 2.0;
+        let path_length = Self::request_distance(vehicle) + self.conflict_box.width() + self.conflict_box.height()
+            + vehicle.length();
+        let slowest_speed = vehicle.junction_speed() / 2.0;
+        let mut windows: HashMap<(usize, usize), (f64, f64)> = HashMap::new();
+        let mut entered = false;
+
+        let (first_step, last_step) = (-(reach / PATH_STEP).ceil() as i64, (path_length / PATH_STEP).ceil() as i64);
+        for step in first_step..=last_step {
+            let distance = step as f64 * PATH_STEP;
+            let (x, y) = vehicle.point_ahead(distance);
+            if !self.conflict_box.overlaps(x, y, half_width) {
+                if entered {
+                    break;
+                }
+                continue;
+            }
+            entered = true;
+
+            let earliest = now + travel_time(
+                (distance - reach).max(0.0), vehicle.speed, vehicle.max_acceleration, vehicle.max_speed,
+            );
+            let latest = now + travel_time(
+                (distance + reach).max(0.0), vehicle.speed, vehicle.max_acceleration / 2.0, slowest_speed,
+            );
+            for tile in self.tiles_at(x, y, half_width) {
+                let window = windows.entry(tile).or_insert((earliest, latest));
+                window.0 = window.0.min(earliest);
+                window.1 = window.1.max(latest);
+            }
+        }
+        windows
+    }
+
+    /// Distance from the box at which the vehicle starts asking for a reservation: far enough to stop
+    /// with its front at the stop line, from cruise speed at its class's comfortable deceleration, if
+    /// refused.
+    fn request_distance(vehicle: &Vehicle) -> f64 {
+        let stop_line_to_box = vehicle.length() / 2.0 + CROSSWALK_WIDTH + STOP_LINE_WIDTH;
+        let speed = vehicle.cruise_speed();
+        stop_line_to_box + speed * speed / (2.0 * vehicle.comfortable_deceleration) + REQUEST_MARGIN
+    }
+
+    /// Checks the plan against every other vehicle's reservations.
+    fn is_free(&self, vehicle_id: u32, plan: &HashMap<(usize, usize), (f64, f64)>) -> bool {
+        plan.iter().all(|(tile, &(from_time, to_time))| {
+            self.tiles.get(tile).is_none_or(|reservations| {
+                reservations.iter().all(|r| {
+                    r.vehicle_id == vehicle_id
+                        || from_time - RESERVATION_MARGIN > r.to_time
+                        || to_time + RESERVATION_MARGIN < r.from_time
+                })
+            })
+        })
+    }
+
+    /// Drops the vehicle's reservation and its grant.
+    fn release(&mut self, vehicle_id: u32) {
+        for reservations in self.tiles.values_mut() {
+            reservations.retain(|r| r.vehicle_id != vehicle_id);
+        }
+        self.granted.remove(&vehicle_id);
+    }
+
+    /// Replaces the vehicle's reservation with `plan`; an empty plan, once the route no longer touches
+    /// the box, releases it.
+    fn reserve(&mut self, vehicle_id: u32, plan: HashMap<(usize, usize), (f64, f64)>) {
+        self.release(vehicle_id);
+        if plan.is_empty() {
+            return;
+        }
+        for (tile, (from_time, to_time)) in plan {
+            self.tiles.entry(tile).or_default().push(TileReservation { vehicle_id, from_time, to_time });
+        }
+        self.granted.insert(vehicle_id);
+    }
+}
+
+impl Default for ReservationController {
+    fn default() -> Self {
+        Self::new(&IntersectionGeometry::default())
+    }
+}
+
+impl IntersectionController for ReservationController {
+    fn name(&self) -> &'static str {
+        "reservation"
+    }
+
+    fn decide(&mut self, vehicles: &[&Vehicle], clock: &SimClock) -> Vec<Command> {
+        let now = clock.now().as_secs_f64();
+        // Forget vehicles that have left the simulation.
+        let present: HashSet<u32> = vehicles.iter().map(|v| v.id).collect();
+        let gone: Vec<u32> = self.granted.iter().copied().filter(|id| !present.contains(id)).collect();
+        for id in gone {
+            self.release(id);
+        }
+
+        // Refresh the reservations of vehicles already granted from where they are now, so delays
+        // extend them. Vehicles that can no longer stop before their stop line are committed and take
+        // the tiles their refreshed plan needs first; any other vehicle whose refreshed plan is no
+        // longer free gives up its grant and asks again below, holding at the stop line if refused.
+        let committed = |vehicle: &Vehicle| {
+            vehicle.occupies_intersection() || vehicle.braking_distance() >= vehicle.distance_to_stop_line()
+        };
+        let (first, then): (Vec<&Vehicle>, Vec<&Vehicle>) = vehicles.iter()
+            .copied()
+            .filter(|vehicle| self.granted.contains(&vehicle.id))
+            .partition(|vehicle| committed(vehicle));
+        for vehicle in first {
+            let plan = self.plan(vehicle, now);
+            self.reserve(vehicle.id, plan);
+        }
+        for vehicle in then {
+            let plan = self.plan(vehicle, now);
+            if self.is_free(vehicle.id, &plan) {
+                self.reserve(vehicle.id, plan);
+            } else {
+                self.release(vehicle.id);
+            }
+        }
+
+        // Requests are served in spawn order, which is the order of `vehicles`.
+        vehicles.iter()
+            .map(|vehicle| {
+                let distance = vehicle.distance_to_intersection();
+                if self.granted.contains(&vehicle.id)
+                    || vehicle.is_in_intersection()
+                    || distance <= 0.0
+                    || distance > Self::request_distance(vehicle)
+                {
+                    return Command::Proceed;
+                }
+
+                let plan = self.plan(vehicle, now);
+                if self.is_free(vehicle.id, &plan) {
+                    self.reserve(vehicle.id, plan);
+                    Command::Proceed
+                } else {
+                    Command::StopWithin(vehicle.distance_to_stop_line())
+                }
+            })
+            .collect()
+    }
+}
+
+/// Seconds needed to cover `distance` starting at `speed` and accelerating at `acceleration`
+/// up to `top_speed`.
+fn travel_time(distance: f64, speed: f64, acceleration: f64, top_speed: f64) -> f64 {
+    let speed = speed.min(top_speed);
+    let acceleration_time = (top_speed - speed) / acceleration;
+    let acceleration_distance = speed * acceleration_time + acceleration * acceleration_time.powi(2) / 2.0;
+    if distance <= acceleration_distance {
+        (-speed + (speed * speed + 2.0 * acceleration * distance).sqrt()) / acceleration
+    } else {
+        acceleration_time + (distance - acceleration_distance) / top_speed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    use crate::road::{LaneKind, Movement, RoadNetwork};
+    use crate::vehicle_class::VehicleClass;
+
+    /// A vehicle standing on the first lane of `approach` going `movement`, `distance` meters before
+    /// the box.
+    fn vehicle(
+        id: u32,
+        network: &Rc<RoadNetwork>,
+        class: VehicleClass,
+        (approach, movement): (Approach, Movement),
+        distance: f64,
+    ) -> Vehicle {
+        let start = network.inbound_lanes(0, approach, movement)[0];
+        let route = network
+            .route(start, 1, |next| {
+                *next.iter()
+                    .find(|&&lane| network.lane(lane).kind == LaneKind::Connector { junction: 0, movement })
+                    .unwrap_or(&next[0])
+            })
+            .unwrap();
+        let max_speed = class.top_speed_range().1;
+        let mut vehicle =
+            Vehicle::new(id, class, approach, movement, network.clone(), route, Duration::ZERO, max_speed);
+        vehicle.offset = network.lane(start).length() - distance;
+        vehicle.speed = 0.0;
+        vehicle
+    }
+
+    /// How two vehicles crossed a junction together.
+    struct Crossing {
+        /// Whether either vehicle was ever told to stop.
+        refused: bool,
+        /// Whether a vehicle told to stop was past its stop line.
+        overran: bool,
+        /// Whether both centers were ever inside the box at the same time.
+        together: bool,
+        /// Closest distance in meters between the two bodies.
+        closest: f64,
+    }
+
+    /// Drives two cars, standing the same distance from the box, through a single junction.
+    fn cross(first: (Approach, Movement), second: (Approach, Movement)) -> Crossing {
+        let geometry = IntersectionGeometry::default();
+        let network = Rc::new(RoadNetwork::new(std::slice::from_ref(&geometry), |_, _| None));
+        let vehicles = vec![
+            vehicle(1, &network, VehicleClass::Car, first, 20.0),
+            vehicle(2, &network, VehicleClass::Car, second, 20.0),
+        ];
+        drive(&geometry, vehicles)
+    }
+
+    /// Drives two vehicles through a single junction under the reservation controller.
+    fn drive(geometry: &IntersectionGeometry, mut vehicles: Vec<Vehicle>) -> Crossing {
+        let mut controller = ReservationController::new(geometry);
+        let mut clock = SimClock::default();
+        let mut crossing = Crossing { refused: false, overran: false, together: false, closest: f64::INFINITY };
+        for _ in 0..1000 {
+            let commands = controller.decide(&vehicles.iter().collect::<Vec<_>>(), &clock);
+            crossing.refused |= commands.iter().any(|&command| command != Command::Proceed);
+            for (i, command) in commands.into_iter().enumerate() {
+                let (before, rest) = vehicles.split_at_mut(i);
+                let (vehicle, after) = rest.split_first_mut().unwrap();
+                let others: Vec<&Vehicle> = before.iter().chain(after.iter()).collect();
+                vehicle.update(&others, command, &clock);
+                crossing.overran |= command != Command::Proceed && vehicle.distance_to_stop_line() < -1e-6;
+            }
+            clock.advance();
+            if let [a, b] = vehicles.as_slice() {
+                crossing.together |= a.is_in_intersection() && b.is_in_intersection();
+                crossing.closest = crossing.closest.min(a.footprint().distance(&b.footprint()));
+            }
+            vehicles.retain(|v| !v.is_finished());
+            if vehicles.is_empty() {
+                return crossing;
+            }
+        }
+        panic!("Vehicles did not leave the junction");
+    }
+
+    #[test]
+    fn conflicting_paths_are_serialized() {
+        for (first, second) in [
+            ((Approach::North, Movement::Straight), (Approach::East, Movement::Straight)),
+            ((Approach::South, Movement::Left), (Approach::North, Movement::Straight)),
+        ] {
+            let crossing = cross(first, second);
+            assert!(crossing.refused, "{:?} and {:?} were both let through", first, second);
+            assert!(!crossing.overran, "{:?} or {:?} was held past its stop line", first, second);
+            assert!(crossing.closest > 0.5, "{:?} and {:?} came {} m apart", first, second, crossing.closest);
+        }
+    }
+
+    #[test]
+    fn non_conflicting_paths_cross_together() {
+        for (first, second) in [
+            ((Approach::North, Movement::Straight), (Approach::South, Movement::Straight)),
+            ((Approach::East, Movement::Right), (Approach::West, Movement::Right)),
+        ] {
+            let crossing = cross(first, second);
+            assert!(!crossing.refused, "{:?} and {:?} were held", first, second);
+            assert!(crossing.together, "{:?} and {:?} did not cross together", first, second);
+        }
+    }
+
+    #[test]
+    fn refused_vehicles_stop_at_the_stop_line_from_cruise_speed() {
+        let geometry = IntersectionGeometry::default();
+        let network = Rc::new(RoadNetwork::new(std::slice::from_ref(&geometry), |_, _| None));
+        for class in VehicleClass::ALL {
+            // A car waiting close to the box takes it first; the vehicle crossing its path arrives at
+            // cruise speed and is refused as soon as it asks.
+            let first = vehicle(1, &network, VehicleClass::Car, (Approach::North, Movement::Straight), 8.0);
+            let mut second = vehicle(2, &network, class, (Approach::East, Movement::Straight), 0.0);
+            second.speed = second.cruise_speed();
+            second.offset = network.lane(second.lane).length() - ReservationController::request_distance(&second);
+            let crossing = drive(&geometry, vec![first, second]);
+            assert!(crossing.refused, "The {} was let through", class);
+            assert!(!crossing.overran, "The {} was held past its stop line", class);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use crate::road::{Approach, JunctionId, Movement};
+use crate::safety::{Measures, Severity};
+use crate::vehicle_class::VehicleClass;
+
+/// Something that happened to a vehicle during a tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    Spawned { class: VehicleClass, approach: Approach, movement: Movement },
+    /// The vehicle entered the approach zone in front of a junction.
+    Approaching { junction: JunctionId },
+    EnteredIntersection { junction: JunctionId },
+    ExitedIntersection { junction: JunctionId },
+    /// The vehicle, at `position`, came within the safety gap of `other`, their footprints `distance`
+    /// meters apart.
+    CloseCall { position: (f64, f64), other: u32, distance: f64 },
+    /// The footprints of the vehicle, at `position`, and of `other`, at `other_position`, overlap.
+    Collision { position: (f64, f64), other: u32, other_position: (f64, f64) },
+    /// The encounter of the vehicle with `other` ended as a conflict of `severity`, with the worst
+    /// `measures` seen during it.
+    Conflict { other: u32, severity: Severity, measures: Measures },
+    /// The vehicle stands still waiting for `waiting_for` in a circular wait that no vehicle can leave.
+    Gridlock { waiting_for: u32 },
+    /// The vehicle keeps no gap to `over` to break a gridlock, and the vehicles in its way make way.
+    PriorityOverride { over: u32 },
+    /// The vehicle may drive on whatever its controller says, as it held up `holding`, standing in a
+    /// junction while no vehicle moved.
+    HoldReleased { holding: u32 },
+    /// The vehicle came too close to a pedestrian crossing the road.
+    PedestrianConflict { pedestrian: u32 },
+    /// The vehicle left the simulation bounds and was removed.
+    Finished,
+}
+
+/// A lifecycle event emitted by the `World`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldEvent {
+    /// Simulation tick the event happened on.
+    pub tick: u64,
+    pub vehicle_id: u32,
+    pub kind: EventKind,
+}
+
+impl fmt::Display for WorldEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[tick {}] vehicle {}: ", self.tick, self.vehicle_id)?;
+        match self.kind {
+            EventKind::Spawned { class, approach, movement } => {
+                write!(f, "spawned {} from {} going {}", class, approach, movement)
+            }
+            EventKind::Approaching { junction } => write!(f, "approaching intersection {}", junction),
+            EventKind::EnteredIntersection { junction } => write!(f, "entered intersection {}", junction),
+            EventKind::ExitedIntersection { junction } => write!(f, "exited intersection {}", junction),
+            EventKind::CloseCall { position, other, distance } => write!(
+                f,
+                "close call at ({:.1}, {:.1}) with vehicle {}, {:.2} m apart",
+                position.0, position.1, other, distance,
+            ),
+            EventKind::Collision { position, other, other_position } => write!(
+                f,
+                "collided at ({:.1}, {:.1}) with vehicle {} at ({:.1}, {:.1})",
+                position.0, position.1, other, other_position.0, other_position.1,
+            ),
+            EventKind::Conflict { other, severity, measures } => {
+                write!(f, "{} conflict with vehicle {}: {}", severity, other, measures)
+            }
+            EventKind::Gridlock { waiting_for } => write!(f, "gridlocked waiting for vehicle {}", waiting_for),
+            EventKind::PriorityOverride { over } => {
+                write!(f, "given priority over vehicle {} to break the gridlock", over)
+            }
+            EventKind::HoldReleased { holding } => {
+                write!(f, "released from its hold to end a standstill holding up vehicle {}", holding)
+            }
+            EventKind::PedestrianConflict { pedestrian } => write!(f, "conflict with pedestrian {}", pedestrian),
+            EventKind::Finished => write!(f, "finished"),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+/// Rectangle turned to a heading: the ground a vehicle covers, or a safety zone around it.
+///
+/// Overlaps between footprints are found with the separating axis theorem: two convex shapes are
+/// apart exactly when their projections onto one of their edge normals do not overlap, and a
+/// rectangle only has two of those.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Footprint {
+    pub center: (f64, f64),
+    /// Heading of the length axis in degrees, as for `Vehicle::angle`.
+    pub angle: f64,
+    pub half_length: f64,
+    pub half_width: f64,
+}
+
+impl Footprint {
+    pub fn new(center: (f64, f64), angle: f64, length: f64, width: f64) -> Self {
+        Footprint { center, angle, half_length: length / 2.0, half_width: width / 2.0 }
+    }
+
+    /// The footprint grown by `margin` on every side.
+    pub fn inflated(self, margin: f64) -> Self {
+        Footprint { half_length: self.half_length + margin, half_width: self.half_width + margin, ..self }
+    }
+
+    /// The footprint lengthened by `margin` at the front only, its back staying in place.
+    pub fn extended_forward(self, margin: f64) -> Self {
+        let (heading, _) = self.axes();
+        Footprint {
+            center: (self.center.0 + heading.0 * margin / 2.0, self.center.1 + heading.1 * margin / 2.0),
+            half_length: self.half_length + margin / 2.0,
+            ..self
+        }
+    }
+
+    /// Distance from the center to the corners; nothing of the footprint lies further out.
+    pub fn radius(&self) -> f64 {
+        self.half_length.hypot(self.half_width)
+    }
+
+    /// Unit vectors along the length and across the width.
+    fn axes(&self) -> ((f64, f64), (f64, f64)) {
+        let rad = self.angle.to_radians();
+        let (heading_x, heading_y) = (rad.cos(), rad.sin());
+        ((heading_x, heading_y), (-heading_y, heading_x))
+    }
+
+    /// The four corners, going around the rectangle.
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        let ((lx, ly), (wx, wy)) = self.axes();
+        let (l, w) = (self.half_length, self.half_width);
+        let (cx, cy) = self.center;
+        [
+            (cx + lx * l + wx * w, cy + ly * l + wy * w),
+            (cx + lx * l - wx * w, cy + ly * l - wy * w),
+            (cx - lx * l - wx * w, cy - ly * l - wy * w),
+            (cx - lx * l + wx * w, cy - ly * l + wy * w),
+        ]
+    }
+
+    /// Interval covered on the line through the origin along the unit vector `axis`.
+    fn project(&self, axis: (f64, f64)) -> (f64, f64) {
+        let (along, across) = self.axes();
+        let center = dot(self.center, axis);
+        let radius = self.half_length * dot(along, axis).abs() + self.half_width * dot(across, axis).abs();
+        (center - radius, center + radius)
+    }
+
+    /// Checks if the two footprints share any ground; touching edges do not count.
+    pub fn overlaps(&self, other: &Footprint) -> bool {
+        let (a, b) = self.axes();
+        let (c, d) = other.axes();
+        [a, b, c, d].into_iter().all(|axis| {
+            let (min, max) = self.project(axis);
+            let (other_min, other_max) = other.project(axis);
+            min < other_max && other_min < max
+        })
+    }
+
+    /// Checks if `point` is inside the footprint.
+    pub fn contains(&self, point: (f64, f64)) -> bool {
+        let (along, across) = self.axes();
+        let offset = (point.0 - self.center.0, point.1 - self.center.1);
+        dot(offset, along).abs() < self.half_length && dot(offset, across).abs() < self.half_width
+    }
+
+    /// Shortest distance between the two footprints, zero if they overlap.
+    ///
+    /// Two rectangles that are apart are closest at a corner of one of them, so only the corners of
+    /// each need measuring against the edges of the other.
+    pub fn distance(&self, other: &Footprint) -> f64 {
+        if self.overlaps(other) {
+            return 0.0;
+        }
+        let corner_to_edges = |corners: [(f64, f64); 4], edges: [(f64, f64); 4]| {
+            corners.into_iter()
+                .flat_map(|corner| (0..4).map(move |i| segment_distance(corner, edges[i], edges[(i + 1) % 4])))
+                .fold(f64::INFINITY, f64::min)
+        };
+        let (corners, other_corners) = (self.corners(), other.corners());
+        corner_to_edges(corners, other_corners).min(corner_to_edges(other_corners, corners))
+    }
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+/// Distance from `point` to the segment from `start` to `end`.
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let segment = (end.0 - start.0, end.1 - start.1);
+    let offset = (point.0 - start.0, point.1 - start.1);
+    let t = (dot(offset, segment) / dot(segment, segment)).clamp(0.0, 1.0);
+    (offset.0 - segment.0 * t).hypot(offset.1 - segment.1 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_corners_overlap_only_when_they_cross() {
+        // 2 m squares turned by 45°, with a corner of each pointing at the other along the x axis.
+        let reach = 2.0_f64.sqrt();
+        let a = Footprint::new((0.0, 0.0), 45.0, 2.0, 2.0);
+        assert!(a.overlaps(&Footprint::new((2.0 * reach - 0.1, 0.0), 45.0, 2.0, 2.0)));
+        assert!(!a.overlaps(&Footprint::new((2.0 * reach + 0.1, 0.0), 45.0, 2.0, 2.0)));
+        // Side by side along the diagonal, their bounding boxes overlap but their sides are apart.
+        let diagonal = Footprint::new((1.5, 1.5), 45.0, 2.0, 2.0);
+        assert!(!a.overlaps(&diagonal) && !diagonal.overlaps(&a));
+        assert!(a.overlaps(&Footprint::new((1.3, 1.3), 45.0, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn vehicles_in_adjacent_lanes_do_not_overlap() {
+        // A 10 m bus and a 4.5 m car side by side, 3.5 m apart center to center.
+        let bus = Footprint::new((0.0, 0.0), 180.0, 10.0, 2.5);
+        let car = Footprint::new((2.0, 3.5), 180.0, 4.5, 1.8);
+        assert!(!bus.overlaps(&car));
+        assert!((bus.distance(&car) - (3.5 - 1.25 - 0.9)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn touching_edges_do_not_overlap() {
+        let a = Footprint::new((0.0, 0.0), 0.0, 4.0, 2.0);
+        let behind = Footprint::new((-4.0, 0.0), 0.0, 4.0, 2.0);
+        let beside = Footprint::new((1.0, 2.0), 0.0, 4.0, 2.0);
+        assert!(!a.overlaps(&behind) && !a.overlaps(&beside));
+        assert_eq!(a.distance(&behind), 0.0);
+        assert!(a.overlaps(&Footprint::new((-3.99, 0.0), 0.0, 4.0, 2.0)));
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::clock::TICKS_PER_SECOND;
+
+/// Ticks the vehicles of a circular wait must all have stood still for before it counts as a gridlock,
+/// when `--gridlock-ticks` is not given.
+pub const DEFAULT_GRIDLOCK_TICKS: u64 = 5 * TICKS_PER_SECOND;
+
+/// Which vehicle of a gridlock is given priority over the vehicle it waits for, to break it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GridlockPolicy {
+    /// The vehicle with the lowest id, i.e. the first spawned.
+    #[default]
+    LowestId,
+    /// The vehicle that has stood still the longest.
+    LongestWait,
+}
+
+impl GridlockPolicy {
+    /// Parses the name used on the command line: `id` or `age`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "id" => Ok(GridlockPolicy::LowestId),
+            "age" => Ok(GridlockPolicy::LongestWait),
+            _ => Err(format!("Invalid gridlock policy: {} (expected id or age)", name)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GridlockPolicy::LowestId => "id",
+            GridlockPolicy::LongestWait => "age",
+        }
+    }
+
+    /// Picks the vehicle of `cycle` that goes first, given the ticks each vehicle has stood still for.
+    pub fn pick(self, cycle: &[u32], stopped_ticks: impl Fn(u32) -> u64) -> u32 {
+        match self {
+            GridlockPolicy::LowestId => cycle.iter().copied().min().unwrap(),
+            // Ties go to the lower id, so the pick does not depend on where the cycle starts.
+            GridlockPolicy::LongestWait => {
+                cycle.iter().copied().max_by_key(|&id| (stopped_ticks(id), std::cmp::Reverse(id))).unwrap()
+            }
+        }
+    }
+}
+
+impl fmt::Display for GridlockPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A circular wait that was found and broken.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gridlock {
+    /// Tick the gridlock was found on.
+    pub tick: u64,
+    /// Ids of the vehicles in the cycle, each waiting for the next and the last for the first, starting
+    /// from the lowest id.
+    pub cycle: Vec<u32>,
+    /// Vehicle given priority over the one it waits for.
+    pub released: u32,
+}
+
+impl fmt::Display for Gridlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for id in &self.cycle {
+            write!(f, "{} -> ", id)?;
+        }
+        write!(f, "{} at tick {}, vehicle {} released", self.cycle[0], self.tick, self.released)
+    }
+}
+
+/// Vehicles standing still in a junction while no vehicle on the map moved, and the vehicle found
+/// holding them up and let past whatever held it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standstill {
+    /// Tick the standstill was found on.
+    pub tick: u64,
+    /// Ids of the vehicles in junctions waiting for `released`, directly or through others, lowest first.
+    pub held: Vec<u32>,
+    /// Vehicle let past the controller holding it.
+    pub released: u32,
+}
+
+impl fmt::Display for Standstill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let held = self.held.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+        write!(f, "vehicle {} released at tick {}, holding up {}", self.released, self.tick, held)
+    }
+}
+
+/// Vehicle at the end of the waits from `start` in the wait-for graph `waits`: the first one waiting
+/// for no other vehicle. `None` if the waits run into a cycle.
+pub fn end_of_waits(waits: &HashMap<u32, u32>, start: u32) -> Option<u32> {
+    let mut seen = HashSet::new();
+    let mut current = start;
+    while let Some(&next) = waits.get(&current) {
+        if !seen.insert(current) {
+            return None;
+        }
+        current = next;
+    }
+    Some(current)
+}
+
+/// Cycles of the wait-for graph `waits`, which maps a vehicle to the vehicle it waits for. Each cycle
+/// starts from its lowest id, and cycles come in order of those ids.
+pub fn find_cycles(waits: &HashMap<u32, u32>) -> Vec<Vec<u32>> {
+    let mut starts: Vec<u32> = waits.keys().copied().collect();
+    starts.sort_unstable();
+    let mut visited = HashSet::new();
+    let mut cycles = Vec::new();
+    for start in starts {
+        // Every vehicle waits for at most one other, so following the waits from any vehicle either
+        // runs out or ends in a cycle.
+        let mut path = Vec::new();
+        let mut current = Some(start);
+        while let Some(id) = current.filter(|&id| visited.insert(id)) {
+            path.push(id);
+            current = waits.get(&id).copied();
+        }
+        // A walk that reaches a vehicle seen in an earlier walk joins that walk's end, not a new cycle.
+        if let Some(position) = current.and_then(|end| path.iter().position(|&id| id == end)) {
+            let mut cycle = path.split_off(position);
+            let lowest = cycle.iter().enumerate().min_by_key(|&(_, &id)| id).map(|(i, _)| i).unwrap();
+            cycle.rotate_left(lowest);
+            cycles.push(cycle);
+        }
+    }
+    cycles.sort();
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waits(edges: &[(u32, u32)]) -> HashMap<u32, u32> {
+        edges.iter().copied().collect()
+    }
+
+    #[test]
+    fn two_vehicles_waiting_for_each_other_are_a_cycle() {
+        assert_eq!(find_cycles(&waits(&[(4, 2), (2, 4)])), vec![vec![2, 4]]);
+    }
+
+    #[test]
+    fn a_tail_feeding_into_a_cycle_is_left_out() {
+        // 1 waits for 9, which is in the cycle 9 -> 5 -> 7 -> 9, and 3 waits for 1.
+        let found = find_cycles(&waits(&[(3, 1), (1, 9), (9, 5), (5, 7), (7, 9)]));
+        assert_eq!(found, vec![vec![5, 7, 9]]);
+    }
+
+    #[test]
+    fn disjoint_cycles_are_all_found_in_order_of_their_lowest_id() {
+        let found = find_cycles(&waits(&[(8, 6), (6, 8), (2, 3), (3, 4), (4, 2), (10, 2)]));
+        assert_eq!(found, vec![vec![2, 3, 4], vec![6, 8]]);
+    }
+
+    #[test]
+    fn cycles_start_from_their_lowest_id() {
+        assert_eq!(find_cycles(&waits(&[(7, 3), (3, 12), (12, 7)])), vec![vec![3, 12, 7]]);
+    }
+
+    #[test]
+    fn chains_ending_at_a_free_vehicle_are_no_cycle() {
+        let chain = waits(&[(1, 2), (2, 3)]);
+        assert!(find_cycles(&chain).is_empty());
+        assert_eq!(end_of_waits(&chain, 1), Some(3));
+        assert_eq!(end_of_waits(&chain, 3), Some(3));
+        assert_eq!(end_of_waits(&waits(&[(1, 2), (2, 3), (3, 2)]), 1), None);
+    }
+
+    #[test]
+    fn policies_pick_the_lowest_id_or_the_longest_wait() {
+        let stopped_ticks = |id: u32| match id {
+            3 => 400,
+            5 => 900,
+            8 => 900,
+            _ => 0,
+        };
+        assert_eq!(GridlockPolicy::LowestId.pick(&[8, 3, 5], stopped_ticks), 3);
+        assert_eq!(GridlockPolicy::LongestWait.pick(&[3, 5, 8], stopped_ticks), 5);
+        // Equal waits go to the lower id, wherever the cycle starts.
+        assert_eq!(GridlockPolicy::LongestWait.pick(&[8, 3, 5], stopped_ticks), 5);
+    }
+}
@@ -35,14 +35,39 @@ pub fn draw_hud(canvas: &mut Canvas<Window>, ttf_context: &Sdl2TtfContext, auto_
     canvas.copy(&auto_texture, None, Some(auto_rect)).unwrap();
 }
 
-pub fn draw_panel(canvas: &mut Canvas<Window>,
-    passed_vehicles: u32,
-    max_velocity: f64,
-    min_velocity: f64,
-    max_time: String,
-    min_time: String,
-    close_calls: u32,
-    ttf_context: &Sdl2TtfContext) {
+/// End-of-run statistics shown by `draw_panel` and printed by `print_panel`.
+#[derive(Debug, PartialEq)]
+pub struct PanelStats {
+    pub passed_vehicles: u32,
+    /// Highest average speed of a vehicle over its trip, in km/h.
+    pub max_velocity: f64,
+    /// Lowest average speed of a vehicle over its trip, in km/h.
+    pub min_velocity: f64,
+    pub max_time: String,
+    pub min_time: String,
+    pub close_calls: u32,
+    /// Times two vehicles' footprints overlapped.
+    pub collisions: u32,
+    /// Vehicle conflicts per severity and the worst time-to-collision, post-encroachment time and
+    /// deceleration rate to avoid the crash.
+    pub conflicts: String,
+    /// Seed of the run, so it can be reproduced with `--seed`.
+    pub seed: u64,
+    /// Name of the intersection controller used for the run.
+    pub controller: String,
+    /// Vehicles passed per approach, e.g. "North 3, East 1, South 2, West 0".
+    pub passed_by_approach: String,
+    /// Vehicles passed per movement, e.g. "Left 2, Straight 3, Right 1".
+    pub passed_by_movement: String,
+    /// One line per junction with its crossings, crossing times, close calls and collisions.
+    pub junctions: Vec<String>,
+    /// Pedestrians crossed, their waits on the curb and their conflicts with vehicles.
+    pub pedestrians: String,
+    /// Gridlocks broken, the policy that broke them and their cycles of waiting vehicles.
+    pub gridlocks: String,
+}
+
+pub fn draw_panel(canvas: &mut Canvas<Window>, stats: &PanelStats, ttf_context: &Sdl2TtfContext) {
     // Load the fonts.  The context is passed in, so it's valid.
     let title_font = ttf_context.load_font("./src/assets/fonts/Roboto-Bold.ttf", 32).unwrap();
     let regular_font = ttf_context.load_font("./src/assets/fonts/Roboto-Regular.ttf", 24).unwrap();
@@ -54,7 +79,7 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
 
     // Drawing the panel
     canvas.set_draw_color(panel_color);
-    let panel_rect = Rect::new(180, 100, 450, 400);
+    let panel_rect = Rect::new(180, 60, 450, 540);
     let _ = canvas.fill_rect(panel_rect);
 
     let texture_creator = canvas.texture_creator();
@@ -66,7 +91,7 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let return_texture = texture_creator
         .create_texture_from_surface(&return_text)
         .unwrap();
-    let return_rect = Rect::new(200, 110, 200, 40);
+    let return_rect = Rect::new(200, 70, 200, 40);
     canvas.copy(&return_texture, None, Some(return_rect)).unwrap();
 
     // Drawing the title text
@@ -77,11 +102,11 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let texture = texture_creator
         .create_texture_from_surface(&surface)
         .unwrap();
-    let text_rect = Rect::new(300, 160, 200, 40);
+    let text_rect = Rect::new(300, 120, 200, 40);
     canvas.copy(&texture, None, Some(text_rect)).unwrap();
 
     //  Drawing the vehicles passed text
-    let vehicles_text = format!("Max number of vehicles passed intersection: {}", passed_vehicles);
+    let vehicles_text = format!("Max number of vehicles passed intersection: {}", stats.passed_vehicles);
     let vehicles_surface = regular_font
         .render(&vehicles_text)
         .blended(title_color)
@@ -89,11 +114,11 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let vehicles_texture = texture_creator
         .create_texture_from_surface(&vehicles_surface)
         .unwrap();
-    let vehicles_rect = Rect::new(250, 220, 300, 30);
+    let vehicles_rect = Rect::new(250, 180, 300, 30);
     canvas.copy(&vehicles_texture, None, Some(vehicles_rect)).unwrap();
 
     // Drawing max velocity of all vehicles text
-    let max_velocity_text = format!("Max velocity of all vehicles passed intersection: {} Units", max_velocity);
+    let max_velocity_text = format!("Max velocity of all vehicles passed intersection: {} km/h", stats.max_velocity);
     let max_velocity_surface = regular_font
         .render(&max_velocity_text)
         .blended(title_color)
@@ -101,11 +126,11 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let max_velocity_texture = texture_creator
         .create_texture_from_surface(&max_velocity_surface)
         .unwrap();
-    let max_velocity_rect = Rect::new(250, 260, 300, 30);
+    let max_velocity_rect = Rect::new(250, 210, 300, 30);
     canvas.copy(&max_velocity_texture, None, Some(max_velocity_rect)).unwrap();
 
     // Drawing min velocity of all vehicles text
-    let min_velocity_text = format!("Min velocity of all vehicles passed intersection: {} Units", min_velocity);
+    let min_velocity_text = format!("Min velocity of all vehicles passed intersection: {} km/h", stats.min_velocity);
     let min_velocity_surface = regular_font
         .render(&min_velocity_text)
         .blended(title_color)
@@ -113,11 +138,11 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let min_velocity_texture = texture_creator
         .create_texture_from_surface(&min_velocity_surface)
         .unwrap();
-    let min_velocity_rect = Rect::new(250, 300, 300, 30);
+    let min_velocity_rect = Rect::new(250, 240, 300, 30);
     canvas.copy(&min_velocity_texture, None, Some(min_velocity_rect)).unwrap();
 
     // Drawing max time that the vehicle took to pass the intersection text
-    let max_time_text = format!("Max time that the vehicle took to pass the intersection: {} seconds", max_time);
+    let max_time_text = format!("Max time that the vehicle took to pass the intersection: {} seconds", stats.max_time);
     let max_time_surface = regular_font
         .render(&max_time_text)
         .blended(title_color)
@@ -125,11 +150,11 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let max_time_texture = texture_creator
         .create_texture_from_surface(&max_time_surface)
         .unwrap();
-    let max_time_rect = Rect::new(250, 340, 300, 30);
+    let max_time_rect = Rect::new(250, 270, 300, 30);
     canvas.copy(&max_time_texture, None, Some(max_time_rect)).unwrap();
 
     // Drawing min time that the vehicle took to pass the intersection text
-    let min_time_text = format!("Min time that the vehicle took to pass the intersection: {} seconds", min_time);
+    let min_time_text = format!("Min time that the vehicle took to pass the intersection: {} seconds", stats.min_time);
     let min_time_surface = regular_font
         .render(&min_time_text)
         .blended(title_color)
@@ -137,11 +162,11 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let min_time_texture = texture_creator
         .create_texture_from_surface(&min_time_surface)
         .unwrap();
-    let min_time_rect = Rect::new(250, 380, 300, 30);
+    let min_time_rect = Rect::new(250, 300, 300, 30);
     canvas.copy(&min_time_texture, None, Some(min_time_rect)).unwrap();
 
     // Drawing the close calls when two vehicles were close to each (less than safe distance) other text
-    let close_calls_text = format!("Close calls when two vehicles were close to each other (less than safe distance): {}", close_calls);
+    let close_calls_text = format!("Close calls when two vehicles were close to each other (less than safe distance): {}", stats.close_calls);
     let close_calls_surface = regular_font
         .render(&close_calls_text)
         .blended(title_color)
@@ -149,12 +174,129 @@ pub fn draw_panel(canvas: &mut Canvas<Window>,
     let close_calls_texture = texture_creator
         .create_texture_from_surface(&close_calls_surface)
         .unwrap();
-    let close_calls_rect = Rect::new(200, 420, 400, 30);
+    let close_calls_rect = Rect::new(200, 330, 400, 30);
     canvas.copy(&close_calls_texture, None, Some(close_calls_rect)).unwrap();
 
+    // Drawing the collisions, when two vehicles' footprints overlapped
+    let collisions_text = format!("Collisions when two vehicles overlapped: {}", stats.collisions);
+    let collisions_surface = regular_font
+        .render(&collisions_text)
+        .blended(title_color)
+        .unwrap();
+    let collisions_texture = texture_creator
+        .create_texture_from_surface(&collisions_surface)
+        .unwrap();
+    let collisions_rect = Rect::new(200, 360, 400, 30);
+    canvas.copy(&collisions_texture, None, Some(collisions_rect)).unwrap();
+
+    // Drawing the conflicts grouped by severity, with the worst surrogate safety measures
+    let conflicts_surface = regular_font
+        .render(&stats.conflicts)
+        .blended(title_color)
+        .unwrap();
+    let conflicts_texture = texture_creator
+        .create_texture_from_surface(&conflicts_surface)
+        .unwrap();
+    let conflicts_rect = Rect::new(200, 390, 400, 30);
+    canvas.copy(&conflicts_texture, None, Some(conflicts_rect)).unwrap();
+
+    // Drawing the seed and controller of the run
+    let seed_text = format!("Seed: {}   Controller: {}", stats.seed, stats.controller);
+    let seed_surface = regular_font
+        .render(&seed_text)
+        .blended(title_color)
+        .unwrap();
+    let seed_texture = texture_creator
+        .create_texture_from_surface(&seed_surface)
+        .unwrap();
+    let seed_rect = Rect::new(200, 420, 200, 30);
+    canvas.copy(&seed_texture, None, Some(seed_rect)).unwrap();
+
+    // Drawing the vehicles passed per approach
+    let approach_text = format!("Passed by approach: {}", stats.passed_by_approach);
+    let approach_surface = regular_font
+        .render(&approach_text)
+        .blended(title_color)
+        .unwrap();
+    let approach_texture = texture_creator
+        .create_texture_from_surface(&approach_surface)
+        .unwrap();
+    let approach_rect = Rect::new(200, 450, 400, 30);
+    canvas.copy(&approach_texture, None, Some(approach_rect)).unwrap();
+
+    // Drawing the vehicles passed per movement
+    let movement_text = format!("Passed by movement: {}", stats.passed_by_movement);
+    let movement_surface = regular_font
+        .render(&movement_text)
+        .blended(title_color)
+        .unwrap();
+    let movement_texture = texture_creator
+        .create_texture_from_surface(&movement_surface)
+        .unwrap();
+    let movement_rect = Rect::new(200, 480, 400, 30);
+    canvas.copy(&movement_texture, None, Some(movement_rect)).unwrap();
+
+    // Drawing the per-junction stats
+    if !stats.junctions.is_empty() {
+        let junctions_text = stats.junctions.join("; ");
+        let junctions_surface = regular_font
+            .render(&junctions_text)
+            .blended(title_color)
+            .unwrap();
+        let junctions_texture = texture_creator
+            .create_texture_from_surface(&junctions_surface)
+            .unwrap();
+        let junctions_rect = Rect::new(200, 510, 400, 25);
+        canvas.copy(&junctions_texture, None, Some(junctions_rect)).unwrap();
+    }
+
+    // Drawing the pedestrian stats
+    let pedestrians_surface = regular_font
+        .render(&stats.pedestrians)
+        .blended(title_color)
+        .unwrap();
+    let pedestrians_texture = texture_creator
+        .create_texture_from_surface(&pedestrians_surface)
+        .unwrap();
+    let pedestrians_rect = Rect::new(200, 540, 400, 25);
+    canvas.copy(&pedestrians_texture, None, Some(pedestrians_rect)).unwrap();
+
+    // Drawing the gridlocks and how they were broken
+    let gridlocks_surface = regular_font
+        .render(&stats.gridlocks)
+        .blended(title_color)
+        .unwrap();
+    let gridlocks_texture = texture_creator
+        .create_texture_from_surface(&gridlocks_surface)
+        .unwrap();
+    let gridlocks_rect = Rect::new(200, 570, 400, 25);
+    canvas.copy(&gridlocks_texture, None, Some(gridlocks_rect)).unwrap();
+
     // Drawing the border
     canvas.set_draw_color(border_color);
     let _ = canvas.draw_rect(panel_rect);
     canvas.present(); // Important: Present the canvas to show changes
 
-}
\ No newline at end of file
+}
+
+/// Prints the same statistics as `draw_panel` to stdout, for runs without a window.
+pub fn print_panel(stats: &PanelStats) {
+    println!("Simulation Complete");
+    println!("Max number of vehicles passed intersection: {}", stats.passed_vehicles);
+    println!("Max velocity of all vehicles passed intersection: {} km/h", stats.max_velocity);
+    println!("Min velocity of all vehicles passed intersection: {} km/h", stats.min_velocity);
+    println!("Max time that the vehicle took to pass the intersection: {} seconds", stats.max_time);
+    println!("Min time that the vehicle took to pass the intersection: {} seconds", stats.min_time);
+    println!("Close calls when two vehicles were close to each other (less than safe distance): {}", stats.close_calls);
+    println!("Collisions when two vehicles overlapped: {}", stats.collisions);
+    println!("{}", stats.conflicts);
+    println!("Seed: {}", stats.seed);
+    println!("Controller: {}", stats.controller);
+    println!("Passed by approach: {}", stats.passed_by_approach);
+    println!("Passed by movement: {}", stats.passed_by_movement);
+    for junction in &stats.junctions {
+        println!("{}", junction);
+    }
+    println!("{}", stats.pedestrians);
+    println!("{}", stats.gridlocks);
+}
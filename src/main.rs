@@ -5,18 +5,208 @@ use sdl2::surface::Surface;
 use sdl2::image::LoadSurface;
 use sdl2::image::LoadTexture;
 use std::time::Duration;
-#[allow(unused_imports)]
-use std::time::Instant;
 
+mod clock;
+mod controller;
+mod event;
+mod footprint;
+mod gridlock;
+mod pedestrian;
 mod road;
-//mod traffic_light;
+mod safety;
+mod scenario;
+mod spatial;
+mod traffic_light;
 mod vehicle;
+mod vehicle_class;
+mod view;
 mod world;
 
 pub use world::World;
-pub use smart_road::{draw_panel, draw_hud};
+use road::{Approach, JunctionKind, LaneLayout};
+use vehicle_class::{VehicleClass, VehicleMix};
+use gridlock::{GridlockPolicy, DEFAULT_GRIDLOCK_TICKS};
+use safety::SeverityThresholds;
+use scenario::Scenario;
+use view::View;
+pub use smart_road::{draw_panel, draw_hud, print_panel};
+use clock::TICKS_PER_SECOND;
+
+/// Length of a headless run when neither `--ticks` nor `--seconds` is given.
+const DEFAULT_HEADLESS_TICKS: u64 = 60 * TICKS_PER_SECOND;
+
+/// Command line options.
+///
+/// * `--headless` - run without a window or audio and print the stats at the end
+/// * `--ticks N` - number of ticks to simulate in headless mode
+/// * `--seconds N` - number of simulated seconds to run in headless mode
+/// * `--seed N` - seed for the world's random source (random when omitted)
+/// * `--log-events` - print every vehicle lifecycle event in headless mode
+/// * `--halt-on-collision` - freeze the simulation on the first collision, outlining the two vehicles
+/// * `--controller NAME` - intersection controller to use (default `fcfs`)
+/// * `--signal-plan PATH` - phase plan file for the `signal` controller
+/// * `--safety-thresholds PATH` - severity thresholds file conflicts between vehicles are grouped by
+/// * `--scenario NAME` - `single` (default), `corridor:N` or `grid:RxC`
+/// * `--lanes LAYOUT` - lanes of each approach, e.g. `"N:L,S,SR E:L,L,S,R"`, or `"S:-"` for a
+///   T-junction without a south leg (see `LaneLayout`)
+/// * `--junction KIND` - `cross` (default) or `roundabout`
+/// * `--pedestrians N` - pedestrians spawned per simulated minute (default 0)
+/// * `--gridlock-ticks N` - ticks the vehicles of a circular wait must all stand still for to count as a
+///   gridlock, and no vehicle may move for to count as a standstill (default 300)
+/// * `--gridlock-policy POLICY` - vehicle of a gridlock given priority, or of a standstill released from
+///   its hold, to break it: `id` (lowest id, the default) or `age` (waited longest)
+/// * `--vehicle-mix MIX` - share of each vehicle class, e.g. `"car:70,truck:10,bus:5,motorcycle:15"`
+///   (cars only by default, see `VehicleMix`)
+struct Options {
+    headless: bool,
+    log_events: bool,
+    halt_on_collision: bool,
+    ticks: u64,
+    seed: u64,
+    controller: String,
+    signal_plan: Option<String>,
+    safety_thresholds: SeverityThresholds,
+    scenario: Scenario,
+    lanes: Option<LaneLayout>,
+    junction: JunctionKind,
+    pedestrians: f64,
+    vehicle_mix: Option<VehicleMix>,
+    gridlock_ticks: u64,
+    gridlock_policy: GridlockPolicy,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            headless: false,
+            log_events: false,
+            halt_on_collision: false,
+            ticks: DEFAULT_HEADLESS_TICKS,
+            seed: rand::random(),
+            controller: "fcfs".to_string(),
+            signal_plan: None,
+            safety_thresholds: SeverityThresholds::default(),
+            scenario: Scenario::default(),
+            lanes: None,
+            junction: JunctionKind::default(),
+            pedestrians: 0.0,
+            vehicle_mix: None,
+            gridlock_ticks: DEFAULT_GRIDLOCK_TICKS,
+            gridlock_policy: GridlockPolicy::default(),
+        };
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--log-events" => options.log_events = true,
+                "--halt-on-collision" => options.halt_on_collision = true,
+                "--ticks" => options.ticks = parse_value(&arg, args.next())?,
+                "--controller" => options.controller = parse_value(&arg, args.next())?,
+                "--signal-plan" => options.signal_plan = Some(parse_value(&arg, args.next())?),
+                "--safety-thresholds" => {
+                    let path: String = parse_value(&arg, args.next())?;
+                    options.safety_thresholds = SeverityThresholds::from_file(&path)?;
+                }
+                "--seed" => options.seed = parse_value(&arg, args.next())?,
+                "--scenario" => {
+                    let name: String = parse_value(&arg, args.next())?;
+                    options.scenario = Scenario::parse(&name)?;
+                }
+                "--lanes" => {
+                    let layout: String = parse_value(&arg, args.next())?;
+                    options.lanes = Some(LaneLayout::parse(&layout)?);
+                }
+                "--junction" => {
+                    let kind: String = parse_value(&arg, args.next())?;
+                    options.junction = JunctionKind::parse(&kind)?;
+                }
+                "--pedestrians" => {
+                    options.pedestrians = parse_value(&arg, args.next())?;
+                    if options.pedestrians < 0.0 {
+                        return Err(format!("Invalid value for {}: {}", arg, options.pedestrians));
+                    }
+                }
+                "--vehicle-mix" => {
+                    let mix: String = parse_value(&arg, args.next())?;
+                    options.vehicle_mix = Some(VehicleMix::parse(&mix)?);
+                }
+                "--gridlock-ticks" => {
+                    options.gridlock_ticks = parse_value(&arg, args.next())?;
+                    if options.gridlock_ticks == 0 {
+                        return Err(format!("Invalid value for {}: 0", arg));
+                    }
+                }
+                "--gridlock-policy" => {
+                    let policy: String = parse_value(&arg, args.next())?;
+                    options.gridlock_policy = GridlockPolicy::parse(&policy)?;
+                }
+                "--seconds" => {
+                    let seconds: f64 = parse_value(&arg, args.next())?;
+                    options.ticks = (seconds * TICKS_PER_SECOND as f64).round() as u64;
+                }
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+        // Applied last so that they hold whatever the order of the arguments.
+        let scenario = &mut options.scenario;
+        scenario.kind = options.junction;
+        if let Some(mix) = &options.vehicle_mix {
+            scenario.vehicle_mix = mix.clone();
+        }
+        scenario.lanes = match (options.junction, options.lanes.clone()) {
+            (JunctionKind::Roundabout, Some(_)) => {
+                return Err("--lanes only applies to cross junctions: roundabouts have one lane per approach".to_string());
+            }
+            (JunctionKind::Roundabout, None) => LaneLayout::single(),
+            (JunctionKind::Cross, lanes) => lanes.unwrap_or_default(),
+        };
+        scenario.validate()?;
+        Ok(options)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", flag))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
 
 fn main() -> Result<(), String> {
+    let options = Options::parse(std::env::args())?;
+    println!("Seed: {}", options.seed);
+    if options.headless {
+        run_headless(&options)
+    } else {
+        run_windowed(&options)
+    }
+}
+
+/// Steps the world with auto spawning enabled for the requested number of ticks, then prints the stats.
+fn run_headless(options: &Options) -> Result<(), String> {
+    let mut world = World::headless(options.seed, options.scenario.clone());
+    world.set_controllers(|geometry| {
+        controller::from_name(&options.controller, options.signal_plan.as_deref(), geometry)
+    })?;
+    world.set_pedestrian_demand(options.pedestrians);
+    world.set_halt_on_collision(options.halt_on_collision);
+    world.set_severity_thresholds(options.safety_thresholds.clone());
+    world.set_gridlock_resolution(options.gridlock_ticks, options.gridlock_policy);
+    if options.log_events {
+        world.subscribe(|event| println!("{}", event));
+    }
+    for _ in 0..options.ticks {
+        world.auto_spawn();
+        world.auto_spawn_pedestrians();
+        world.update();
+        if let Some((first, second)) = world.halted_on() {
+            println!("Halted after {} ticks on a collision between vehicles {} and {}", world.clock().tick(), first, second);
+            break;
+        }
+    }
+    print_panel(&world.panel_stats());
+    Ok(())
+}
+
+fn run_windowed(options: &Options) -> Result<(), String> {
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
@@ -36,10 +226,11 @@ fn main() -> Result<(), String> {
     //     .set_color_key(true, Color::RGB(255, 255, 255))
     //     .map_err(|e| e.to_string())?;
 
-    // Create a texture creator from the canvas and load the sprite sheet.
+    // Create a texture creator from the canvas and load the sprite of every vehicle class.
     let texture_creator = canvas.texture_creator();
-    let sprite_texture = texture_creator
-        .load_texture("./src/assets/sprite.png")
+    let sprites = VehicleClass::ALL.iter()
+        .map(|class| texture_creator.load_texture(class.sprite()))
+        .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
     // let sprite_texture = texture_creator
     //     .create_texture_from_surface(&surface)
@@ -51,7 +242,16 @@ fn main() -> Result<(), String> {
     // Initialize the TTF context here, and keep it alive for the whole program.
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
 
-    let mut world = World::new(&sdl_context);
+    let mut world = World::new(&sdl_context, options.seed, options.scenario.clone());
+    world.set_controllers(|geometry| {
+        controller::from_name(&options.controller, options.signal_plan.as_deref(), geometry)
+    })?;
+    world.set_pedestrian_demand(options.pedestrians);
+    world.set_halt_on_collision(options.halt_on_collision);
+    world.set_severity_thresholds(options.safety_thresholds.clone());
+    world.set_gridlock_resolution(options.gridlock_ticks, options.gridlock_policy);
+    // The world is in meters; fit the whole scenario into the window. The HUD and panel are drawn in pixels.
+    let view = View::fit(options.scenario.extent(), canvas.output_size()?);
     let mut auto_spawning = false;
 
     'running: loop {
@@ -63,13 +263,7 @@ fn main() -> Result<(), String> {
                     ..
                 } => {
                     // Pass the ttf_context to draw_panel.
-                    draw_panel(&mut canvas, world.get_vehicles_passed(),
-                    world.get_max_velocity(),
-                    world.get_min_velocity(),
-                    world.max_vehicles_time(),
-                    world.min_vehicles_time(),
-                    world.get_total_close_call_count(),
-                    &ttf_context);
+                    draw_panel(&mut canvas, &world.panel_stats(), &ttf_context);
                     // Wait for user input to close
                     loop {
                         for event in event_pump.poll_iter() {
@@ -81,7 +275,7 @@ fn main() -> Result<(), String> {
                                 _ => {}
                             }
                         }
-                        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+                        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / TICKS_PER_SECOND as u32));
                     }
                     //break 'running
                 },
@@ -93,10 +287,11 @@ fn main() -> Result<(), String> {
                         Keycode::R => {
                             auto_spawning = !auto_spawning;
                         },
-                        Keycode::Down if !auto_spawning => world.spawn_dir(1),
-                        Keycode::Up if !auto_spawning => world.spawn_dir(0),
-                        Keycode::Right if !auto_spawning => world.spawn_dir(3),
-                        Keycode::Left if !auto_spawning => world.spawn_dir(2),
+                        // Arrow keys give the direction of travel, so Up spawns from the south.
+                        Keycode::Down if !auto_spawning => world.spawn_dir(Approach::North),
+                        Keycode::Up if !auto_spawning => world.spawn_dir(Approach::South),
+                        Keycode::Right if !auto_spawning => world.spawn_dir(Approach::West),
+                        Keycode::Left if !auto_spawning => world.spawn_dir(Approach::East),
                         _ => {}
                     }
                 }
@@ -107,17 +302,18 @@ fn main() -> Result<(), String> {
         if auto_spawning {
             world.auto_spawn();
         }
+        world.auto_spawn_pedestrians();
 
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
 
         //world.auto_spawn();
         world.update();
-        world.draw(&mut canvas, &sprite_texture)?;
+        world.draw(&mut canvas, &sprites, &view)?;
         draw_hud(&mut canvas, &ttf_context, auto_spawning);
 
         canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / TICKS_PER_SECOND as u32));
     }
 
     Ok(())
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use sdl2::gfx::primitives::DrawRenderer;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::road::{Crosswalk, JunctionId};
+use crate::view::View;
+
+/// Walking speed in m/s.
+pub const WALKING_SPEED: f64 = 1.4;
+/// Radius of a drawn pedestrian in meters.
+const PEDESTRIAN_RADIUS: f64 = 0.4;
+
+/// A pedestrian crossing one leg of a junction on its crosswalk.
+///
+/// Pedestrians spawn on a curb and wait there until the `World` lets them start; once on the
+/// crosswalk they walk to the other curb at a steady pace and leave the simulation.
+#[derive(Clone, Debug)]
+pub struct Pedestrian {
+    /// Unique within a `World`, assigned in spawn order.
+    pub id: u32,
+    pub junction: JunctionId,
+    /// Index of the crosswalk in the `World`.
+    pub crosswalk_index: usize,
+    pub crosswalk: Crosswalk,
+    /// Walking from the crosswalk's `end` to its `start` rather than the other way.
+    pub reversed: bool,
+    /// Distance walked along the crosswalk.
+    pub walked: f64,
+    pub x: f64,
+    pub y: f64,
+    /// Simulation time at which the pedestrian was spawned.
+    pub spawn_time: Duration,
+    /// Simulation time at which the pedestrian stepped onto the crosswalk; `None` while waiting.
+    pub crossing_since: Option<Duration>,
+}
+
+impl Pedestrian {
+    pub fn new(
+        id: u32,
+        junction: JunctionId,
+        crosswalk_index: usize,
+        crosswalk: Crosswalk,
+        reversed: bool,
+        spawn_time: Duration,
+    ) -> Self {
+        let (x, y) = if reversed { crosswalk.end } else { crosswalk.start };
+        Pedestrian { id, junction, crosswalk_index, crosswalk, reversed, walked: 0.0, x, y, spawn_time, crossing_since: None }
+    }
+
+    pub fn is_crossing(&self) -> bool {
+        self.crossing_since.is_some()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.walked >= self.crosswalk.length()
+    }
+
+    /// Distance of the pedestrian from the crosswalk's `start`.
+    pub fn position(&self) -> f64 {
+        if self.reversed {
+            self.crosswalk.length() - self.walked
+        } else {
+            self.walked
+        }
+    }
+
+    /// Checks if the pedestrian is walking towards the point `distance` from the crosswalk's `start`.
+    pub fn is_walking_towards(&self, distance: f64) -> bool {
+        if self.reversed {
+            distance < self.position()
+        } else {
+            distance > self.position()
+        }
+    }
+
+    /// Steps onto the crosswalk at simulation time `now`.
+    pub fn start_crossing(&mut self, now: Duration) {
+        self.crossing_since.get_or_insert(now);
+    }
+
+    /// Walks for `dt` seconds if crossing.
+    pub fn update(&mut self, dt: f64) {
+        if !self.is_crossing() {
+            return;
+        }
+        self.walked = (self.walked + WALKING_SPEED * dt).min(self.crosswalk.length());
+        (self.x, self.y) = self.crosswalk.point_at(self.position());
+    }
+
+    /// Renders the pedestrian as a dot, placed on the window by `view`.
+    pub fn draw(&self, canvas: &mut Canvas<Window>, view: &View) -> Result<(), String> {
+        let (x, y) = view.point(self.x, self.y);
+        let radius = view.length(PEDESTRIAN_RADIUS);
+        canvas.filled_circle(x as i16, y as i16, radius as i16, sdl2::pixels::Color::RGB(255, 140, 0))
+    }
+}
@@ -1,2 +1,1021 @@
-pub const ROAD_WIDTH: u32 = 240;
-pub const ROAD_HEIGHT: u32 = 800;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Width of a lane in meters.
+pub const LANE_WIDTH: f64 = 4.0;
+/// Length of the north and south roads leading to the single intersection, in meters.
+pub const VERTICAL_APPROACH_LENGTH: f64 = 18.0;
+/// Length of the east and west roads leading to the single intersection, in meters.
+pub const HORIZONTAL_APPROACH_LENGTH: f64 = 28.0;
+/// Thickness of a stop line in meters, drawn just before the crosswalk.
+pub const STOP_LINE_WIDTH: f64 = 0.4;
+/// Width of the crosswalk across each leg, between its stop line and the conflict box, in meters.
+pub const CROSSWALK_WIDTH: f64 = 3.0;
+/// How far from the edge of the road pedestrians wait to cross, in meters.
+const CURB_OFFSET: f64 = 0.5;
+/// Speed limit on the roads leading to and from a junction, in m/s (36 km/h).
+pub const ROAD_SPEED_LIMIT: f64 = 36.0 / 3.6;
+/// Speed limit on the connectors through a junction, in m/s (25 km/h).
+pub const JUNCTION_SPEED_LIMIT: f64 = 25.0 / 3.6;
+/// Speed limit around a roundabout, in m/s (20 km/h).
+pub const ROUNDABOUT_SPEED_LIMIT: f64 = 20.0 / 3.6;
+/// Radius of the middle of a roundabout's circulating lane, in meters.
+pub const ROUNDABOUT_RADIUS: f64 = 8.0;
+/// Radius of the curves leading onto and off a roundabout, in meters.
+const ROUNDABOUT_CURVE_RADIUS: f64 = 6.0;
+
+/// The side of the intersection a vehicle enters from.
+///
+/// A vehicle with `Approach::South` spawns at the bottom of the map and drives north.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Approach {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Approach {
+    pub const ALL: [Approach; 4] = [Approach::North, Approach::East, Approach::South, Approach::West];
+
+    /// Angle in degrees (world coordinates, y pointing down) a vehicle from this approach faces on spawn.
+    pub fn heading_angle(self) -> f64 {
+        match self {
+            Approach::North => 90.0,  // Driving south
+            Approach::East => 180.0,  // Driving west
+            Approach::South => 270.0, // Driving north
+            Approach::West => 0.0,    // Driving east
+        }
+    }
+
+    /// Parses the letter used in config files: `N`, `E`, `S` or `W`.
+    pub fn from_letter(letter: &str) -> Option<Approach> {
+        match letter {
+            "N" => Some(Approach::North),
+            "E" => Some(Approach::East),
+            "S" => Some(Approach::South),
+            "W" => Some(Approach::West),
+            _ => None,
+        }
+    }
+
+    /// Position in `ALL`.
+    pub fn index(self) -> usize {
+        Approach::ALL.iter().position(|&a| a == self).unwrap()
+    }
+
+    /// The side across the intersection.
+    pub fn opposite(self) -> Approach {
+        self.exit(Movement::Straight)
+    }
+
+    /// Side of the intersection a vehicle from this approach leaves through after `movement`.
+    pub fn exit(self, movement: Movement) -> Approach {
+        let index = self.index();
+        // `ALL` runs clockwise, and a left turn from the north leaves through the east.
+        let turns = match movement {
+            Movement::Left => 1,
+            Movement::Straight => 2,
+            Movement::Right => 3,
+        };
+        Approach::ALL[(index + turns) % Approach::ALL.len()]
+    }
+}
+
+impl fmt::Display for Approach {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Approach::North => "North",
+            Approach::East => "East",
+            Approach::South => "South",
+            Approach::West => "West",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Which way a vehicle turns through the intersection, from the driver's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Movement {
+    Left,
+    Straight,
+    Right,
+}
+
+impl Movement {
+    pub const ALL: [Movement; 3] = [Movement::Left, Movement::Straight, Movement::Right];
+
+    /// Parses the letter used in config files: `L`, `S` or `R`.
+    pub fn from_letter(letter: char) -> Option<Movement> {
+        match letter {
+            'L' => Some(Movement::Left),
+            'S' => Some(Movement::Straight),
+            'R' => Some(Movement::Right),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Movement::Left => "Left",
+            Movement::Straight => "Straight",
+            Movement::Right => "Right",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Unit vector pointing along `angle` degrees (world coordinates, y pointing down).
+fn unit(angle: f64) -> (f64, f64) {
+    let rad = angle * PI / 180.0;
+    (rad.cos(), rad.sin())
+}
+
+/// Heading in degrees of the vector `(dx, dy)`.
+fn angle_of(dx: f64, dy: f64) -> f64 {
+    dy.atan2(dx) * 180.0 / PI
+}
+
+/// A piece of a path: a straight line or a circular arc.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    Line { start: (f64, f64), heading: f64, length: f64 },
+    /// Arc around `center`; the heading changes by `sweep` degrees, positive turning right.
+    Arc { center: (f64, f64), radius: f64, start_heading: f64, sweep: f64 },
+}
+
+impl Segment {
+    pub fn length(&self) -> f64 {
+        match *self {
+            Segment::Line { length, .. } => length,
+            Segment::Arc { radius, sweep, .. } => radius * sweep.abs() * PI / 180.0,
+        }
+    }
+
+    /// Heading in degrees `distance` along the segment.
+    pub fn heading_at(&self, distance: f64) -> f64 {
+        match *self {
+            Segment::Line { heading, .. } => heading,
+            Segment::Arc { start_heading, sweep, .. } => start_heading + sweep * distance / self.length(),
+        }
+    }
+
+    /// Point `distance` along the segment; lines extend past their end.
+    pub fn point_at(&self, distance: f64) -> (f64, f64) {
+        match *self {
+            Segment::Line { start, heading, .. } => {
+                let (dx, dy) = unit(heading);
+                (start.0 + distance * dx, start.1 + distance * dy)
+            }
+            Segment::Arc { center, radius, sweep, .. } => {
+                let (dx, dy) = unit(self.heading_at(distance) - sweep.signum() * 90.0);
+                (center.0 + radius * dx, center.1 + radius * dy)
+            }
+        }
+    }
+}
+
+/// The route a vehicle follows, measured by arc length from its spawn point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    pub segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn length(&self) -> f64 {
+        self.segments.iter().map(Segment::length).sum()
+    }
+
+    /// The segment `distance` falls on and the distance left into it; past the end, the last segment.
+    fn locate(&self, mut distance: f64) -> (&Segment, f64) {
+        let (last, rest) = self.segments.split_last().expect("a path has at least one segment");
+        for segment in rest {
+            if distance < segment.length() {
+                return (segment, distance);
+            }
+            distance -= segment.length();
+        }
+        (last, distance)
+    }
+
+    /// Point `distance` along the path.
+    pub fn point_at(&self, distance: f64) -> (f64, f64) {
+        let (segment, offset) = self.locate(distance);
+        segment.point_at(offset)
+    }
+
+    /// Heading in degrees `distance` along the path.
+    pub fn heading_at(&self, distance: f64) -> f64 {
+        let (segment, offset) = self.locate(distance);
+        segment.heading_at(offset)
+    }
+}
+
+/// Axis-aligned bounds of the area where routes cross, in world coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConflictBox {
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+}
+
+impl ConflictBox {
+    pub fn width(&self) -> f64 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> f64 {
+        self.bottom - self.top
+    }
+
+    /// Checks if the point lies strictly inside the box.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x > self.left && x < self.right && y > self.top && y < self.bottom
+    }
+
+    /// Checks if a square of half side `half_size` centred on the point overlaps the box.
+    pub fn overlaps(&self, x: f64, y: f64, half_size: f64) -> bool {
+        x + half_size > self.left && x - half_size < self.right && y + half_size > self.top && y - half_size < self.bottom
+    }
+}
+
+/// A crosswalk across one leg of a junction, walked from curb to curb.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crosswalk {
+    /// Side of the junction whose road it crosses.
+    pub side: Approach,
+    /// Waiting spot on the curb next to the inbound lanes.
+    pub start: (f64, f64),
+    /// Waiting spot on the curb next to the outbound lanes.
+    pub end: (f64, f64),
+    /// The painted part of the road.
+    pub area: ConflictBox,
+}
+
+impl Crosswalk {
+    pub fn length(&self) -> f64 {
+        (self.end.0 - self.start.0).hypot(self.end.1 - self.start.1)
+    }
+
+    /// Point `distance` from `start` towards `end`.
+    pub fn point_at(&self, distance: f64) -> (f64, f64) {
+        let ratio = distance / self.length();
+        (
+            self.start.0 + (self.end.0 - self.start.0) * ratio,
+            self.start.1 + (self.end.1 - self.start.1) * ratio,
+        )
+    }
+
+    /// Distance from `start` of the point of the crosswalk closest to `(x, y)`.
+    pub fn distance_along(&self, x: f64, y: f64) -> f64 {
+        let (dx, dy) = (self.end.0 - self.start.0, self.end.1 - self.start.1);
+        ((x - self.start.0) * dx + (y - self.start.1) * dy) / self.length()
+    }
+}
+
+/// Number of inbound lanes of every approach and the movements allowed from each of them.
+///
+/// Layouts are written as one group per approach to change, an approach letter (`N`, `E`, `S`, `W`)
+/// followed by its lanes from the center line outwards, each the movements it allows (`L`, `S`, `R`),
+/// e.g. `N:L,S,SR E:L,L,S,R`. Approaches not listed keep one left, one straight and one right lane.
+///
+/// An approach without lanes, written `S:-`, is a missing leg, which makes a three-legged junction.
+/// Approaches not listed then drop the movements that would leave through it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaneLayout {
+    /// Lanes of each approach, indexed like `Approach::ALL`.
+    approaches: [Vec<Vec<Movement>>; 4],
+}
+
+impl Default for LaneLayout {
+    fn default() -> Self {
+        let lanes = vec![vec![Movement::Left], vec![Movement::Straight], vec![Movement::Right]];
+        LaneLayout { approaches: [lanes.clone(), lanes.clone(), lanes.clone(), lanes] }
+    }
+}
+
+impl LaneLayout {
+    /// One lane per approach, allowing every movement, as at a single-lane roundabout.
+    pub fn single() -> Self {
+        let lanes = vec![Movement::ALL.to_vec()];
+        LaneLayout { approaches: [lanes.clone(), lanes.clone(), lanes.clone(), lanes] }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut layout = LaneLayout::default();
+        let mut listed = [false; 4];
+        for group in text.split_whitespace() {
+            let (letter, lanes) = group.split_once(':').ok_or_else(|| format!("Invalid lane group: {}", group))?;
+            let approach = Approach::from_letter(letter).ok_or_else(|| format!("Invalid approach: {}", letter))?;
+            listed[approach.index()] = true;
+            if lanes == "-" {
+                layout.approaches[approach.index()] = Vec::new();
+                continue;
+            }
+            let lanes = lanes.split(',')
+                .map(|lane| {
+                    let movements: Vec<Movement> = lane.chars()
+                        .map(|letter| Movement::from_letter(letter).ok_or_else(|| format!("Invalid movement: {}", letter)))
+                        .collect::<Result<_, _>>()?;
+                    if movements.is_empty() {
+                        return Err(format!("Empty lane for approach {}", approach));
+                    }
+                    Ok(movements)
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            layout.set_lanes(approach, lanes)?;
+        }
+
+        let legs = Approach::ALL.map(|approach| layout.has_leg(approach));
+        for approach in Approach::ALL.into_iter().filter(|approach| !listed[approach.index()]) {
+            let lanes = &mut layout.approaches[approach.index()];
+            for lane in lanes.iter_mut() {
+                lane.retain(|&movement| legs[approach.exit(movement).index()]);
+            }
+            lanes.retain(|lane| !lane.is_empty());
+        }
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    /// Replaces the lanes of `approach`, listed from the center line outwards.
+    ///
+    /// No lane may turn further left than a lane inside it, so paths from the same approach never cross.
+    pub fn set_lanes(&mut self, approach: Approach, lanes: Vec<Vec<Movement>>) -> Result<(), String> {
+        let rank = |movement: &Movement| Movement::ALL.iter().position(|m| m == movement).unwrap();
+        for pair in lanes.windows(2) {
+            if pair[0].iter().map(rank).max() > pair[1].iter().map(rank).min() {
+                return Err(format!("Lanes of approach {} would cross: outer lanes must not turn further left", approach));
+            }
+        }
+        self.approaches[approach.index()] = lanes;
+        Ok(())
+    }
+
+    /// Checks that the junction has at least three legs and that every movement leaves through one of
+    /// them.
+    fn validate(&self) -> Result<(), String> {
+        if Approach::ALL.into_iter().filter(|&approach| self.has_leg(approach)).count() < 3 {
+            return Err("A junction needs at least three legs".to_string());
+        }
+        for approach in Approach::ALL.into_iter().filter(|&approach| self.has_leg(approach)) {
+            for &movement in self.lanes(approach).iter().flatten() {
+                let side = approach.exit(movement);
+                if !self.has_leg(side) {
+                    return Err(format!("{} from {} would leave through the missing {} leg", movement, approach, side));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if the junction has a road on the `approach` side; a missing leg has no lanes.
+    pub fn has_leg(&self, approach: Approach) -> bool {
+        !self.lanes(approach).is_empty()
+    }
+
+    /// Checks if some lane of `approach` allows `movement`.
+    pub fn allows(&self, approach: Approach, movement: Movement) -> bool {
+        self.lanes(approach).iter().flatten().any(|&m| m == movement)
+    }
+
+    /// Lanes of `approach` from the center line outwards, each with the movements it allows.
+    pub fn lanes(&self, approach: Approach) -> &[Vec<Movement>] {
+        &self.approaches[approach.index()]
+    }
+
+    pub fn lane_count(&self, approach: Approach) -> u32 {
+        self.lanes(approach).len() as u32
+    }
+}
+
+/// How the two roads of a junction meet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JunctionKind {
+    /// The roads cross in a box where every movement has its own path.
+    #[default]
+    Cross,
+    /// The roads meet at a one-lane ring driven counterclockwise; vehicles turn right onto it, leave it
+    /// at the exit of their movement, and give way to the vehicles already on it.
+    Roundabout,
+}
+
+impl JunctionKind {
+    /// Parses the name used by `--junction`: `cross` or `roundabout`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "cross" => Ok(JunctionKind::Cross),
+            "roundabout" => Ok(JunctionKind::Roundabout),
+            _ => Err(format!("Invalid junction: {} (expected cross or roundabout)", text)),
+        }
+    }
+}
+
+/// Layout of a junction of two straight roads, four-way or missing one leg.
+///
+/// Everything placed on the map (spawn points, stop lines, routes, the conflict box and the drawn
+/// roads) is derived from these few numbers. Each road carries the inbound lanes of its approach and,
+/// next to them, as many outbound lanes as the opposite approach has inbound ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntersectionGeometry {
+    pub kind: JunctionKind,
+    /// Where the center lines of the two roads cross.
+    pub center: (f64, f64),
+    pub lane_width: f64,
+    /// Length of the north and south roads, from the edge of the map to the conflict box.
+    pub vertical_approach_length: f64,
+    /// Length of the east and west roads, from the edge of the map to the conflict box.
+    pub horizontal_approach_length: f64,
+    pub lanes: LaneLayout,
+}
+
+impl Default for IntersectionGeometry {
+    fn default() -> Self {
+        Self::new(JunctionKind::Cross, LaneLayout::default())
+    }
+}
+
+impl IntersectionGeometry {
+    /// Geometry of a junction of `kind` with the given lanes, placed so that the map starts at the origin.
+    pub fn new(kind: JunctionKind, lanes: LaneLayout) -> Self {
+        let mut geometry = IntersectionGeometry {
+            kind,
+            center: (0.0, 0.0),
+            lane_width: LANE_WIDTH,
+            vertical_approach_length: VERTICAL_APPROACH_LENGTH,
+            horizontal_approach_length: HORIZONTAL_APPROACH_LENGTH,
+            lanes,
+        };
+        geometry.center = (
+            geometry.horizontal_approach_length + geometry.entry_depth(Approach::West),
+            geometry.vertical_approach_length + geometry.entry_depth(Approach::North),
+        );
+        geometry
+    }
+
+    /// Number of inbound lanes of `approach`.
+    pub fn lane_count(&self, approach: Approach) -> u32 {
+        self.lanes.lane_count(approach)
+    }
+
+    pub fn has_leg(&self, side: Approach) -> bool {
+        self.lanes.has_leg(side)
+    }
+
+    /// Number of lanes leaving through `side`: one per inbound lane of the opposite approach or, where
+    /// that leg is missing, as many as the approach turning into `side` with the most lanes doing so.
+    pub fn outbound_lane_count(&self, side: Approach) -> u32 {
+        if !self.has_leg(side) {
+            return 0;
+        }
+        if self.has_leg(side.opposite()) {
+            return self.lane_count(side.opposite());
+        }
+        Approach::ALL.into_iter()
+            .flat_map(|approach| Movement::ALL.into_iter().map(move |movement| (approach, movement)))
+            .filter(|&(approach, movement)| approach.exit(movement) == side)
+            .map(|(approach, movement)| {
+                self.lanes.lanes(approach).iter().filter(|lane| lane.contains(&movement)).count() as u32
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Distance from the center to the edge of the conflict box `approach` enters through.
+    ///
+    /// At a cross that edge is the outer edge of the lanes crossing its path first: the inbound lanes
+    /// of the side its left turn leaves through, or the lanes leaving on the other side of that road if
+    /// there are more of them. A roundabout's box holds the whole ring.
+    fn entry_depth(&self, approach: Approach) -> f64 {
+        match self.kind {
+            JunctionKind::Cross => {
+                let side = approach.exit(Movement::Left);
+                self.lane_count(side).max(self.outbound_lane_count(side.opposite())) as f64 * self.lane_width
+            }
+            JunctionKind::Roundabout => ROUNDABOUT_RADIUS + self.lane_width,
+        }
+    }
+
+    /// Speed limit on the connectors through the junction, in m/s.
+    pub fn connector_speed_limit(&self) -> f64 {
+        match self.kind {
+            JunctionKind::Cross => JUNCTION_SPEED_LIMIT,
+            JunctionKind::Roundabout => ROUNDABOUT_SPEED_LIMIT,
+        }
+    }
+
+    /// Distance from the center line to the middle of the lane, counted from the center line outwards.
+    pub fn lane_offset(&self, lane_index: u32) -> f64 {
+        self.lane_width / 2.0 + self.lane_width * lane_index as f64
+    }
+
+    /// Length of the road from the edge of the map to the conflict box.
+    pub fn approach_length(&self, approach: Approach) -> f64 {
+        match approach {
+            Approach::North | Approach::South => self.vertical_approach_length,
+            Approach::East | Approach::West => self.horizontal_approach_length,
+        }
+    }
+
+    /// The rectangle where the two roads cross.
+    pub fn conflict_box(&self) -> ConflictBox {
+        ConflictBox {
+            left: self.center.0 - self.entry_depth(Approach::West),
+            right: self.center.0 + self.entry_depth(Approach::East),
+            top: self.center.1 - self.entry_depth(Approach::North),
+            bottom: self.center.1 + self.entry_depth(Approach::South),
+        }
+    }
+
+    /// Point `distance` along the road of `approach` from the edge of the map, `offset` to the right of
+    /// its center line.
+    fn approach_point(&self, approach: Approach, offset: f64, distance: f64) -> (f64, f64) {
+        let heading = approach.heading_angle();
+        let (hx, hy) = unit(heading);
+        let (rx, ry) = unit(heading + 90.0);
+        let back = self.approach_length(approach) + self.entry_depth(approach) - distance;
+        (self.center.0 + offset * rx - back * hx, self.center.1 + offset * ry - back * hy)
+    }
+
+    /// Point `distance` along the lane `lane_index` of `approach`, measured from the edge of the map.
+    ///
+    /// Lanes sit on the right of their direction of travel.
+    pub fn lane_point(&self, approach: Approach, lane_index: u32, distance: f64) -> (f64, f64) {
+        self.approach_point(approach, self.lane_offset(lane_index), distance)
+    }
+
+    /// Rectangle `(x, y, width, height)` of the road of `side`, from the edge of the map to the conflict
+    /// box, between two offsets to the right of its center line as seen driving in.
+    pub fn road_rect(&self, side: Approach, from_offset: f64, to_offset: f64) -> (f64, f64, f64, f64) {
+        let (ax, ay) = self.approach_point(side, from_offset, 0.0);
+        let (bx, by) = self.approach_point(side, to_offset, self.approach_length(side));
+        (ax.min(bx), ay.min(by), (bx - ax).abs(), (by - ay).abs())
+    }
+
+    /// Distance from the spawn point to the near edge of the stop line of `approach`.
+    pub fn stop_line_distance(&self, approach: Approach) -> f64 {
+        self.approach_length(approach) - CROSSWALK_WIDTH - STOP_LINE_WIDTH
+    }
+
+    /// Stop line of `approach` as `(x, y, width, height)`, across the lanes entering the intersection.
+    pub fn stop_line(&self, approach: Approach) -> (f64, f64, f64, f64) {
+        let width = self.lane_count(approach) as f64 * self.lane_width;
+        let distance = self.stop_line_distance(approach);
+        let (cx, cy) = self.approach_point(approach, 0.0, distance);
+        let (ex, ey) = self.approach_point(approach, width, distance + STOP_LINE_WIDTH);
+        (cx.min(ex), cy.min(ey), (ex - cx).abs(), (ey - cy).abs())
+    }
+
+    /// Crosswalk across the road of `side`, between its stop line and the conflict box.
+    pub fn crosswalk(&self, side: Approach) -> Crosswalk {
+        let inbound = self.lane_count(side) as f64 * self.lane_width;
+        let outbound = self.outbound_lane_count(side) as f64 * self.lane_width;
+        let length = self.approach_length(side);
+        let (ax, ay) = self.approach_point(side, -outbound, length - CROSSWALK_WIDTH);
+        let (bx, by) = self.approach_point(side, inbound, length);
+        let middle = length - CROSSWALK_WIDTH / 2.0;
+        Crosswalk {
+            side,
+            start: self.approach_point(side, inbound + CURB_OFFSET, middle),
+            end: self.approach_point(side, -outbound - CURB_OFFSET, middle),
+            area: ConflictBox { left: ax.min(bx), right: ax.max(bx), top: ay.min(by), bottom: ay.max(by) },
+        }
+    }
+
+    /// Lane `lane_index` of `approach`, from the edge of the map to the conflict box.
+    pub fn inbound_path(&self, approach: Approach, lane_index: u32) -> Path {
+        Path {
+            segments: vec![Segment::Line {
+                start: self.lane_point(approach, lane_index, 0.0),
+                heading: approach.heading_angle(),
+                length: self.approach_length(approach),
+            }],
+        }
+    }
+
+    /// Lane `lane_index` leaving through `side`, from the conflict box to the edge of the map.
+    pub fn outbound_path(&self, side: Approach, lane_index: u32) -> Path {
+        let heading = (side.heading_angle() + 180.0) % 360.0;
+        let (hx, hy) = unit(heading);
+        let (rx, ry) = unit(heading + 90.0);
+        let offset = self.lane_offset(lane_index);
+        let depth = self.entry_depth(side);
+        Path {
+            segments: vec![Segment::Line {
+                start: (self.center.0 + offset * rx + depth * hx, self.center.1 + offset * ry + depth * hy),
+                heading,
+                length: self.approach_length(side),
+            }],
+        }
+    }
+
+    /// Path of `movement` through the conflict box from the end of lane `lane_index` of `approach` into
+    /// lane `exit_lane` of the road it leaves on.
+    ///
+    /// At a cross, turns are quarter circles, preceded or followed by a straight stretch when the exit
+    /// lane is not the mirror image of the entry lane; going straight into another lane is a diagonal
+    /// line. At a roundabout, every movement drives onto the ring and around it to its exit.
+    pub fn connector_path(&self, approach: Approach, lane_index: u32, movement: Movement, exit_lane: u32) -> Path {
+        if self.kind == JunctionKind::Roundabout {
+            return self.roundabout_path(approach, lane_index, movement, exit_lane);
+        }
+        let start = self.lane_point(approach, lane_index, self.approach_length(approach));
+        let heading = approach.heading_angle();
+        let sweep: f64 = match movement {
+            Movement::Left => -90.0,
+            Movement::Straight => 0.0,
+            Movement::Right => 90.0,
+        };
+        let exit_heading = heading + sweep;
+        let (ox, oy) = unit(exit_heading);
+        let (rx, ry) = unit(exit_heading + 90.0);
+        let exit_offset = self.lane_offset(exit_lane);
+        let depth = self.entry_depth(approach.exit(movement));
+        let end = (
+            self.center.0 + exit_offset * rx + depth * ox,
+            self.center.1 + exit_offset * ry + depth * oy,
+        );
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+
+        if sweep == 0.0 {
+            let length = (dx * dx + dy * dy).sqrt();
+            let heading = dy.atan2(dx) * 180.0 / PI;
+            return Path { segments: vec![Segment::Line { start, heading, length }] };
+        }
+
+        let (hx, hy) = unit(heading);
+        // Distances to cover along the entry heading and along the exit heading.
+        let along_entry = dx * hx + dy * hy;
+        let along_exit = dx * ox + dy * oy;
+        let radius = along_entry.min(along_exit);
+
+        let mut segments = Vec::new();
+        let mut point = start;
+        if along_entry > radius {
+            segments.push(Segment::Line { start, heading, length: along_entry - radius });
+            point = (start.0 + (along_entry - radius) * hx, start.1 + (along_entry - radius) * hy);
+        }
+        let (cx, cy) = unit(heading + sweep.signum() * 90.0);
+        let center = (point.0 + radius * cx, point.1 + radius * cy);
+        segments.push(Segment::Arc { center, radius, start_heading: heading, sweep });
+        if along_exit > radius {
+            // The arc ends `radius` along both headings from where it starts.
+            let arc_end = (point.0 + radius * (hx + ox), point.1 + radius * (hy + oy));
+            segments.push(Segment::Line { start: arc_end, heading: exit_heading, length: along_exit - radius });
+        }
+        Path { segments }
+    }
+
+    /// Curve joining the ring from the lane `offset` to the right of a road's center line, with the
+    /// edge of the conflict box `depth` from the center: its radius, and how far from the center,
+    /// along the road, it leaves the lane.
+    ///
+    /// The curve turns right and touches the ring from outside, so it meets it at the ring's heading;
+    /// its radius shrinks if needed to keep it inside the box. Leaving the ring is the same curve
+    /// mirrored along the road the vehicle leaves on.
+    fn ring_curve(&self, offset: f64, depth: f64) -> (f64, f64) {
+        let ring = ROUNDABOUT_RADIUS;
+        let radius = ROUNDABOUT_CURVE_RADIUS.min((depth * depth - ring * ring + offset * offset) / (2.0 * (ring - offset)));
+        (radius, ((ring + radius).powi(2) - (offset + radius).powi(2)).sqrt())
+    }
+
+    /// Center of the ring curve of radius `radius` for the lane `offset` to the right of a road
+    /// heading `heading`, `along` from the center; negative `along` lies before the center.
+    fn ring_curve_center(&self, heading: f64, offset: f64, radius: f64, along: f64) -> (f64, f64) {
+        let (hx, hy) = unit(heading);
+        let (rx, ry) = unit(heading + 90.0);
+        let side = offset + radius;
+        (self.center.0 + side * rx + along * hx, self.center.1 + side * ry + along * hy)
+    }
+
+    /// Point where the ring curve centred on `curve_center` with radius `radius` touches the ring.
+    fn ring_point(&self, curve_center: (f64, f64), radius: f64) -> (f64, f64) {
+        let scale = ROUNDABOUT_RADIUS / (ROUNDABOUT_RADIUS + radius);
+        (
+            self.center.0 + (curve_center.0 - self.center.0) * scale,
+            self.center.1 + (curve_center.1 - self.center.1) * scale,
+        )
+    }
+
+    /// Point on the ring where vehicles from lane `lane_index` of `approach` merge into the circulating
+    /// traffic.
+    pub fn merge_point(&self, approach: Approach, lane_index: u32) -> (f64, f64) {
+        let offset = self.lane_offset(lane_index);
+        let (radius, along) = self.ring_curve(offset, self.entry_depth(approach));
+        self.ring_point(self.ring_curve_center(approach.heading_angle(), offset, radius, -along), radius)
+    }
+
+    /// Path from the end of lane `lane_index` of `approach`, onto the ring, counterclockwise around it
+    /// and off it into lane `exit_lane` of the side `movement` leaves through.
+    fn roundabout_path(&self, approach: Approach, lane_index: u32, movement: Movement, exit_lane: u32) -> Path {
+        let mut segments = Vec::new();
+
+        // Onto the ring, turning right.
+        let heading = approach.heading_angle();
+        let depth = self.entry_depth(approach);
+        let offset = self.lane_offset(lane_index);
+        let (entry_radius, entry_along) = self.ring_curve(offset, depth);
+        let entry_center = self.ring_curve_center(heading, offset, entry_radius, -entry_along);
+        if depth > entry_along {
+            let start = self.lane_point(approach, lane_index, self.approach_length(approach));
+            segments.push(Segment::Line { start, heading, length: depth - entry_along });
+        }
+        let merge = self.ring_point(entry_center, entry_radius);
+        // On a right turn the heading is a quarter turn past the direction from the curve's center.
+        let ring_heading = angle_of(merge.0 - entry_center.0, merge.1 - entry_center.1) + 90.0;
+        segments.push(Segment::Arc {
+            center: entry_center,
+            radius: entry_radius,
+            start_heading: heading,
+            sweep: (ring_heading - heading).rem_euclid(360.0),
+        });
+
+        // Off the ring, turning right into the road leaving through `side`.
+        let side = approach.exit(movement);
+        let exit_heading = (side.heading_angle() + 180.0) % 360.0;
+        let exit_depth = self.entry_depth(side);
+        let exit_offset = self.lane_offset(exit_lane);
+        let (exit_radius, exit_along) = self.ring_curve(exit_offset, exit_depth);
+        let exit_center = self.ring_curve_center(exit_heading, exit_offset, exit_radius, exit_along);
+        let diverge = self.ring_point(exit_center, exit_radius);
+        let diverge_heading = angle_of(diverge.0 - exit_center.0, diverge.1 - exit_center.1) + 90.0;
+
+        // Around the ring, counterclockwise on the map, so the heading turns left.
+        let merge_angle = angle_of(merge.0 - self.center.0, merge.1 - self.center.1);
+        let diverge_angle = angle_of(diverge.0 - self.center.0, diverge.1 - self.center.1);
+        segments.push(Segment::Arc {
+            center: self.center,
+            radius: ROUNDABOUT_RADIUS,
+            start_heading: ring_heading,
+            sweep: -(merge_angle - diverge_angle).rem_euclid(360.0),
+        });
+
+        segments.push(Segment::Arc {
+            center: exit_center,
+            radius: exit_radius,
+            start_heading: diverge_heading,
+            sweep: (exit_heading - diverge_heading).rem_euclid(360.0),
+        });
+        if exit_depth > exit_along {
+            let (hx, hy) = unit(exit_heading);
+            let (rx, ry) = unit(exit_heading + 90.0);
+            let start = (
+                self.center.0 + exit_offset * rx + exit_along * hx,
+                self.center.1 + exit_offset * ry + exit_along * hy,
+            );
+            segments.push(Segment::Line { start, heading: exit_heading, length: exit_depth - exit_along });
+        }
+        Path { segments }
+    }
+}
+
+/// Index of a junction in its scenario.
+pub type JunctionId = usize;
+
+/// Index of a lane in its `RoadNetwork`.
+pub type LaneId = usize;
+
+/// What part of the network a lane belongs to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LaneKind {
+    /// Leads to `junction` from its `approach` side, either from the edge of the map or from the
+    /// neighbouring junction.
+    Inbound { junction: JunctionId, approach: Approach, lane_index: u32 },
+    /// Runs through the junction, linking an inbound lane to the lane it leaves on.
+    Connector { junction: JunctionId, movement: Movement },
+    /// Leads from the junction to the edge of the map; `side` is the side of the junction it leaves from.
+    Outbound { junction: JunctionId, side: Approach, lane_index: u32 },
+}
+
+/// A directed lane segment.
+#[derive(Clone, Debug)]
+pub struct Lane {
+    pub id: LaneId,
+    pub kind: LaneKind,
+    pub path: Path,
+    /// In m/s.
+    pub speed_limit: f64,
+    /// Lanes a vehicle can continue on from the end of this one.
+    pub next: Vec<LaneId>,
+}
+
+impl Lane {
+    pub fn length(&self) -> f64 {
+        self.path.length()
+    }
+
+    pub fn start(&self) -> (f64, f64) {
+        self.path.point_at(0.0)
+    }
+
+    pub fn end(&self) -> (f64, f64) {
+        self.path.point_at(self.length())
+    }
+
+    pub fn is_connector(&self) -> bool {
+        matches!(self.kind, LaneKind::Connector { .. })
+    }
+
+    /// Junction the lane leads to or runs through; `None` for lanes leaving the map.
+    pub fn junction(&self) -> Option<JunctionId> {
+        match self.kind {
+            LaneKind::Inbound { junction, .. } | LaneKind::Connector { junction, .. } => Some(junction),
+            LaneKind::Outbound { .. } => None,
+        }
+    }
+}
+
+/// Lane graph of the map: vehicles follow a route of lanes, moving from one lane onto the next.
+#[derive(Clone, Debug, Default)]
+pub struct RoadNetwork {
+    lanes: Vec<Lane>,
+}
+
+impl RoadNetwork {
+    /// Builds the lanes of a set of junctions: cross junctions with four legs or three, and roundabouts.
+    ///
+    /// Every junction gets the inbound lanes of the legs its lane layout has, and a connector for each
+    /// movement an inbound lane allows, running around the ring on a roundabout. `neighbour` gives the
+    /// junction on the other side of a junction's side: connectors leading there end in any lane of
+    /// that junction's inbound road, so vehicles can pick their next movement, while sides without a
+    /// neighbour get outbound lanes to the edge of the map.
+    pub fn new(
+        junctions: &[IntersectionGeometry],
+        neighbour: impl Fn(JunctionId, Approach) -> Option<JunctionId>,
+    ) -> Self {
+        let mut network = RoadNetwork::default();
+        for (junction, geometry) in junctions.iter().enumerate() {
+            for approach in Approach::ALL {
+                for lane_index in 0..geometry.lane_count(approach) {
+                    network.add_lane(
+                        LaneKind::Inbound { junction, approach, lane_index },
+                        geometry.inbound_path(approach, lane_index),
+                        ROAD_SPEED_LIMIT,
+                    );
+                }
+            }
+            for side in Approach::ALL.into_iter().filter(|&side| neighbour(junction, side).is_none()) {
+                for lane_index in 0..geometry.outbound_lane_count(side) {
+                    network.add_lane(
+                        LaneKind::Outbound { junction, side, lane_index },
+                        geometry.outbound_path(side, lane_index),
+                        ROAD_SPEED_LIMIT,
+                    );
+                }
+            }
+        }
+
+        for (junction, geometry) in junctions.iter().enumerate() {
+            for approach in Approach::ALL {
+                for (lane_index, movements) in (0..).zip(geometry.lanes.lanes(approach)) {
+                    let Some(inbound) = network.find(LaneKind::Inbound { junction, approach, lane_index }) else {
+                        continue;
+                    };
+                    for &movement in movements {
+                        let side = approach.exit(movement);
+                        let exits: Vec<(u32, LaneId)> = match neighbour(junction, side) {
+                            Some(next) => (0..junctions[next].lane_count(side.opposite()))
+                                .filter_map(|lane| {
+                                    let kind = LaneKind::Inbound { junction: next, approach: side.opposite(), lane_index: lane };
+                                    network.find(kind).map(|id| (lane, id))
+                                })
+                                .collect(),
+                            None => {
+                                // Keep to the same lane where the road leaving has enough of them.
+                                let exit_lane = lane_index.min(geometry.outbound_lane_count(side).saturating_sub(1));
+                                network.find(LaneKind::Outbound { junction, side, lane_index: exit_lane })
+                                    .map(|id| vec![(exit_lane, id)])
+                                    .unwrap_or_default()
+                            }
+                        };
+                        for (exit_lane, exit) in exits {
+                            let connector = network.add_lane(
+                                LaneKind::Connector { junction, movement },
+                                geometry.connector_path(approach, lane_index, movement, exit_lane),
+                                geometry.connector_speed_limit(),
+                            );
+                            network.lanes[inbound].next.push(connector);
+                            network.lanes[connector].next.push(exit);
+                        }
+                    }
+                }
+            }
+        }
+        network
+    }
+
+    fn add_lane(&mut self, kind: LaneKind, path: Path, speed_limit: f64) -> LaneId {
+        let id = self.lanes.len();
+        self.lanes.push(Lane { id, kind, path, speed_limit, next: Vec::new() });
+        id
+    }
+
+    fn find(&self, kind: LaneKind) -> Option<LaneId> {
+        self.lanes.iter().position(|lane| lane.kind == kind)
+    }
+
+    pub fn lane(&self, id: LaneId) -> &Lane {
+        &self.lanes[id]
+    }
+
+    pub fn lanes(&self) -> &[Lane] {
+        &self.lanes
+    }
+
+    /// Inbound lanes of `junction` from `approach` that allow `movement`.
+    pub fn inbound_lanes(&self, junction: JunctionId, approach: Approach, movement: Movement) -> Vec<LaneId> {
+        self.lanes.iter()
+            .filter(|lane| {
+                matches!(lane.kind, LaneKind::Inbound { junction: j, approach: a, .. } if j == junction && a == approach)
+                    && lane.next.iter().any(|&next| self.lanes[next].kind == LaneKind::Connector { junction, movement })
+            })
+            .map(|lane| lane.id)
+            .collect()
+    }
+
+    /// Lanes from `start` until a lane with no successor, letting `choose` pick whenever a lane
+    /// continues on more than one. Fails once the route has run through more than `max_junctions`
+    /// junctions without ending.
+    pub fn route(
+        &self,
+        start: LaneId,
+        max_junctions: usize,
+        mut choose: impl FnMut(&[LaneId]) -> LaneId,
+    ) -> Result<Vec<LaneId>, String> {
+        let mut route = vec![start];
+        let mut lane = &self.lanes[start];
+        let mut junctions = 0;
+        while !lane.next.is_empty() {
+            let next = if lane.next.len() == 1 { lane.next[0] } else { choose(&lane.next) };
+            lane = &self.lanes[next];
+            route.push(next);
+            if lane.is_connector() {
+                junctions += 1;
+                if junctions > max_junctions {
+                    return Err(format!("Route from lane {} runs through more than {} junctions", start, max_junctions));
+                }
+            }
+        }
+        Ok(route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lane_layouts_are_parsed_or_rejected() {
+        for (text, expected) in [
+            ("N:L,S,R", Ok(())),
+            ("N:LS,R E:L,SR", Ok(())),
+            ("N:L,LS,S,SR,R", Ok(())),
+            ("N:S,L", Err("Lanes of approach North would cross: outer lanes must not turn further left")),
+            ("E:LR,S", Err("Lanes of approach East would cross: outer lanes must not turn further left")),
+            ("W:R,LSR", Err("Lanes of approach West would cross: outer lanes must not turn further left")),
+            ("N:L,,R", Err("Empty lane for approach North")),
+            ("N:X", Err("Invalid movement: X")),
+            ("Q:L", Err("Invalid approach: Q")),
+            ("NLSR", Err("Invalid lane group: NLSR")),
+        ] {
+            assert_eq!(LaneLayout::parse(text).map(|_| ()), expected.map_err(str::to_string), "layout {:?}", text);
+        }
+    }
+
+    #[test]
+    fn listed_approaches_get_their_lanes_from_the_center_line_outwards() {
+        let layout = LaneLayout::parse("N:LS,R E:LSR").unwrap();
+        assert_eq!(layout.lanes(Approach::North), [vec![Movement::Left, Movement::Straight], vec![Movement::Right]]);
+        assert_eq!(layout.lanes(Approach::East), [Movement::ALL.to_vec()]);
+        assert_eq!(layout.lanes(Approach::South), LaneLayout::default().lanes(Approach::South));
+    }
+
+    #[test]
+    fn a_missing_leg_drops_the_movements_into_it_from_approaches_not_listed() {
+        let layout = LaneLayout::parse("S:-").unwrap();
+        for approach in Approach::ALL {
+            for movement in Movement::ALL {
+                let expected = approach != Approach::South && approach.exit(movement) != Approach::South;
+                assert_eq!(layout.allows(approach, movement), expected, "{} from {}", movement, approach);
+            }
+        }
+        // The lane left with no movement is removed rather than kept empty.
+        assert_eq!(layout.lane_count(Approach::North), 2);
+        assert!(!layout.has_leg(Approach::South));
+    }
+
+    #[test]
+    fn movements_into_missing_legs_are_rejected() {
+        for (text, expected) in [
+            ("S:- N:L,R", Ok(())),
+            ("S:- N:L,S,R", Err("Straight from North would leave through the missing South leg")),
+            ("S:- E:LSR", Err("Left from East would leave through the missing South leg")),
+            ("N:- S:-", Err("A junction needs at least three legs")),
+        ] {
+            assert_eq!(LaneLayout::parse(text).map(|_| ()), expected.map_err(str::to_string), "layout {:?}", text);
+        }
+    }
+}
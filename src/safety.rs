@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+use crate::footprint::Footprint;
+use crate::road::Approach;
+
+/// Levels used when `--safety-thresholds` is not given; the same as `config/safety_thresholds.txt`.
+const DEFAULT_THRESHOLDS: &str = "\
+serious 1.0 1.0 3.4
+moderate 2.0 2.0 2.0
+slight 3.0 3.0 1.0
+";
+/// Time step in seconds at which vehicles are moved ahead when looking for their time-to-collision.
+const TTC_STEP: f64 = 0.05;
+/// Side in meters of the cells the ground covered by vehicles is tracked in, for post-encroachment times.
+const PET_CELL_SIZE: f64 = 1.0;
+
+/// How close a conflict came to a crash, most severe first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Serious,
+    Moderate,
+    Slight,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Serious, Severity::Moderate, Severity::Slight];
+
+    /// Parses the name used in the thresholds file: `serious`, `moderate` or `slight`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Severity::ALL.into_iter()
+            .find(|severity| severity.name() == name)
+            .ok_or_else(|| format!("Invalid severity: {} (expected serious, moderate or slight)", name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Serious => "serious",
+            Severity::Moderate => "moderate",
+            Severity::Slight => "slight",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Limits at which a conflict reaches a severity: it does when any one of its measures passes its limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thresholds {
+    /// Time-to-collision at or below which the severity is reached, in seconds.
+    pub ttc: f64,
+    /// Post-encroachment time at or below which the severity is reached, in seconds.
+    pub pet: f64,
+    /// Deceleration rate to avoid the crash at or above which the severity is reached, in m/s².
+    pub drac: f64,
+}
+
+/// Thresholds of each severity conflicts are grouped by.
+///
+/// Written one severity per line as its name followed by its TTC, PET and DRAC thresholds, e.g.
+/// `serious 1.0 1.0 3.4`. Severities left out are never reported. Empty lines and lines starting with
+/// `#` are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeverityThresholds {
+    /// Most severe first.
+    levels: Vec<(Severity, Thresholds)>,
+}
+
+impl SeverityThresholds {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read safety thresholds {}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut levels: Vec<(Severity, Thresholds)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let level = Self::parse_level(line).map_err(|e| format!("Safety thresholds line {}: {}", number + 1, e))?;
+            if levels.iter().any(|&(severity, _)| severity == level.0) {
+                return Err(format!("Safety thresholds line {}: {} given twice", number + 1, level.0));
+            }
+            levels.push(level);
+        }
+        if levels.is_empty() {
+            return Err("Safety thresholds have no severity".to_string());
+        }
+        levels.sort_by_key(|&(severity, _)| severity as usize);
+        Ok(SeverityThresholds { levels })
+    }
+
+    fn parse_level(line: &str) -> Result<(Severity, Thresholds), String> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != 4 {
+            return Err("expected a severity followed by TTC, PET and DRAC thresholds".to_string());
+        }
+        let value = |token: &str| -> Result<f64, String> {
+            match token.parse::<f64>() {
+                Ok(value) if value >= 0.0 && value.is_finite() => Ok(value),
+                _ => Err(format!("invalid threshold: {}", token)),
+            }
+        };
+        let thresholds = Thresholds { ttc: value(tokens[1])?, pet: value(tokens[2])?, drac: value(tokens[3])? };
+        Ok((Severity::parse(tokens[0])?, thresholds))
+    }
+
+    /// Longest time-to-collision any severity counts, in seconds.
+    fn max_ttc(&self) -> f64 {
+        self.levels.iter().map(|(_, thresholds)| thresholds.ttc).fold(0.0, f64::max)
+    }
+
+    /// Longest post-encroachment time any severity counts, in seconds.
+    fn max_pet(&self) -> f64 {
+        self.levels.iter().map(|(_, thresholds)| thresholds.pet).fold(0.0, f64::max)
+    }
+
+    /// The highest severity `measures` reach, if any.
+    pub fn classify(&self, measures: &Measures) -> Option<Severity> {
+        self.levels.iter()
+            .find(|(_, level)| {
+                measures.ttc.is_some_and(|ttc| ttc <= level.ttc)
+                    || measures.pet.is_some_and(|pet| pet <= level.pet)
+                    || measures.drac.is_some_and(|drac| drac >= level.drac)
+            })
+            .map(|&(severity, _)| severity)
+    }
+}
+
+impl Default for SeverityThresholds {
+    fn default() -> Self {
+        Self::parse(DEFAULT_THRESHOLDS).unwrap()
+    }
+}
+
+/// Worst surrogate safety measures seen between two vehicles over their encounter.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Measures {
+    /// Shortest time-to-collision, in seconds.
+    pub ttc: Option<f64>,
+    /// Shortest post-encroachment time, in seconds.
+    pub pet: Option<f64>,
+    /// Highest deceleration rate to avoid the crash, in m/s².
+    pub drac: Option<f64>,
+}
+
+impl Measures {
+    fn merge(&mut self, other: Measures) {
+        self.ttc = min(self.ttc, other.ttc);
+        self.pet = min(self.pet, other.pet);
+        self.drac = match (self.drac, other.drac) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+fn min(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+impl fmt::Display for Measures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let measure = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.2}", value));
+        write!(f, "TTC {} s, PET {} s, DRAC {} m/s²", measure(self.ttc), measure(self.pet), measure(self.drac))
+    }
+}
+
+/// Two vehicles from different directions coming within the safety gap of each other, from the tick
+/// they did until they drew apart again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloseCall {
+    /// Ids of the two vehicles, lower first.
+    pub vehicles: (u32, u32),
+    /// Tick the vehicles came within the safety gap.
+    pub started: u64,
+    /// Tick the vehicles were closest.
+    pub closest_tick: u64,
+    /// Midpoint between the two vehicles when they were closest.
+    pub position: (f64, f64),
+    /// Shortest distance between their footprints, in meters.
+    pub min_distance: f64,
+}
+
+/// Where a vehicle is and how it moves, as seen by the `SafetyMonitor`.
+#[derive(Clone, Copy, Debug)]
+pub struct Motion {
+    pub id: u32,
+    pub footprint: Footprint,
+    /// Velocity in m/s, along the footprint's heading.
+    pub velocity: (f64, f64),
+}
+
+/// Time until the two footprints touch if both keep their velocity, looking at most `horizon` seconds
+/// ahead. `None` if they never touch within the horizon, or already do.
+pub fn time_to_collision(a: &Motion, b: &Motion, horizon: f64) -> Option<f64> {
+    if a.footprint.overlaps(&b.footprint) {
+        return None;
+    }
+    // The footprints cannot touch while the circles around them are apart, so only the span of time
+    // the circles overlap, if any, is stepped through.
+    let (dx, dy) = (b.footprint.center.0 - a.footprint.center.0, b.footprint.center.1 - a.footprint.center.1);
+    let (wx, wy) = (b.velocity.0 - a.velocity.0, b.velocity.1 - a.velocity.1);
+    let reach = a.footprint.radius() + b.footprint.radius();
+    let (qa, qb, qc) = (wx * wx + wy * wy, dx * wx + dy * wy, dx * dx + dy * dy - reach * reach);
+    let discriminant = qb * qb - qa * qc;
+    if qa == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let (enter, leave) = ((-qb - discriminant.sqrt()) / qa, (-qb + discriminant.sqrt()) / qa);
+    if leave < 0.0 || enter > horizon {
+        return None;
+    }
+    // Only the relative motion matters: keep `a` in place and move `b` by the difference of velocities.
+    let moved = |t: f64| Footprint {
+        center: (b.footprint.center.0 + wx * t, b.footprint.center.1 + wy * t),
+        ..b.footprint
+    };
+    let first = (enter.max(0.0) / TTC_STEP).floor().max(1.0) as usize;
+    let last = (leave.min(horizon) / TTC_STEP).ceil() as usize;
+    (first..=last)
+        .map(|step| step as f64 * TTC_STEP)
+        .filter(|&t| t <= horizon)
+        .find(|&t| a.footprint.overlaps(&moved(t)))
+}
+
+/// Deceleration that brings the relative speed of the two vehicles to zero before they collide in
+/// `ttc` seconds.
+pub fn deceleration_to_avoid(a: &Motion, b: &Motion, ttc: f64) -> f64 {
+    let relative_speed = (a.velocity.0 - b.velocity.0).hypot(a.velocity.1 - b.velocity.1);
+    relative_speed / (2.0 * ttc)
+}
+
+/// Last vehicle seen on a cell of ground.
+#[derive(Clone, Copy, Debug)]
+struct Occupancy {
+    vehicle: u32,
+    approach: Approach,
+    tick: u64,
+}
+
+/// Measures time-to-collision, post-encroachment time and deceleration rate to avoid the crash
+/// between interacting vehicles, and keeps the worst of each per pair.
+pub struct SafetyMonitor {
+    thresholds: SeverityThresholds,
+    /// Measures of the pairs of vehicles still on the map, lower id first.
+    encounters: HashMap<(u32, u32), Measures>,
+    /// Conflicts of finished encounters, indexed by severity.
+    counts: [u32; 3],
+    /// Worst measures over all conflicts.
+    worst: Measures,
+    /// Last vehicle on each cell of the junction boxes.
+    cells: HashMap<(i32, i32), Occupancy>,
+}
+
+impl SafetyMonitor {
+    pub fn new(thresholds: SeverityThresholds) -> Self {
+        SafetyMonitor {
+            counts: [0; 3],
+            thresholds,
+            encounters: HashMap::new(),
+            worst: Measures::default(),
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Seconds of travel the time-to-collision looks ahead.
+    pub fn horizon(&self) -> f64 {
+        self.thresholds.max_ttc()
+    }
+
+    /// Records the time-to-collision of `a` and `b`, and the deceleration rate to avoid the crash
+    /// that goes with it, if they are on course to collide within the horizon.
+    pub fn observe_pair(&mut self, a: &Motion, b: &Motion) {
+        if let Some(ttc) = time_to_collision(a, b, self.horizon()) {
+            let measures = Measures { ttc: Some(ttc), pet: None, drac: Some(deceleration_to_avoid(a, b, ttc)) };
+            self.record(a.id, b.id, measures);
+        }
+    }
+
+    /// Marks the ground `motion` covers inside a junction at `tick`, recording the
+    /// post-encroachment time to the vehicle from another approach that covered it last.
+    pub fn observe_occupancy(&mut self, motion: &Motion, approach: Approach, tick: u64, dt: f64) {
+        let cell = |value: f64| (value / PET_CELL_SIZE).floor() as i32;
+        let (mut min_x, mut max_x, mut min_y, mut max_y) = (i32::MAX, i32::MIN, i32::MAX, i32::MIN);
+        for (x, y) in motion.footprint.corners() {
+            (min_x, max_x) = (min_x.min(cell(x)), max_x.max(cell(x)));
+            (min_y, max_y) = (min_y.min(cell(y)), max_y.max(cell(y)));
+        }
+        let max_pet = self.thresholds.max_pet();
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let center = ((x as f64 + 0.5) * PET_CELL_SIZE, (y as f64 + 0.5) * PET_CELL_SIZE);
+                if !motion.footprint.contains(center) {
+                    continue;
+                }
+                let occupancy = Occupancy { vehicle: motion.id, approach, tick };
+                if let Some(previous) = self.cells.insert((x, y), occupancy) {
+                    // On the same tick both vehicles cover the cell: that is a collision, not an encroachment.
+                    let pet = tick.saturating_sub(previous.tick) as f64 * dt;
+                    let encroached = previous.vehicle != motion.id && previous.approach != approach;
+                    if encroached && previous.tick < tick && pet <= max_pet {
+                        self.record(previous.vehicle, motion.id, Measures { pet: Some(pet), ..Measures::default() });
+                    }
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, a: u32, b: u32, measures: Measures) {
+        let pair = (a.min(b), a.max(b));
+        self.encounters.entry(pair).or_default().merge(measures);
+    }
+
+    /// Ends the encounters of a vehicle leaving the map, returning those that reached a severity with
+    /// the other vehicle's id.
+    pub fn finish(&mut self, vehicle: u32) -> Vec<(u32, Measures, Severity)> {
+        let pairs: Vec<(u32, u32)> = self.encounters.keys()
+            .copied()
+            .filter(|&(a, b)| a == vehicle || b == vehicle)
+            .collect();
+        let mut finished = Vec::new();
+        for pair in pairs {
+            let conflict = self.encounters.remove(&pair).unwrap();
+            if let Some(severity) = self.thresholds.classify(&conflict) {
+                self.counts[severity as usize] += 1;
+                self.worst.merge(conflict);
+                let other = if pair.0 == vehicle { pair.1 } else { pair.0 };
+                finished.push((other, conflict, severity));
+            }
+        }
+        finished.sort_by_key(|&(other, _, _)| other);
+        finished
+    }
+
+    /// Conflicts per severity and the worst measures, counting the encounters still going on.
+    pub fn summary(&self) -> String {
+        let mut counts = self.counts;
+        let mut worst = self.worst;
+        for conflict in self.encounters.values() {
+            if let Some(severity) = self.thresholds.classify(conflict) {
+                counts[severity as usize] += 1;
+                worst.merge(*conflict);
+            }
+        }
+        let levels = self.thresholds.levels.iter()
+            .map(|&(severity, _)| format!("{} {}", severity, counts[severity as usize]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Conflicts: {}; worst {}", levels, worst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motion(id: u32, center: (f64, f64), angle: f64, speed: f64) -> Motion {
+        let rad = f64::to_radians(angle);
+        Motion {
+            id,
+            footprint: Footprint::new(center, angle, 4.0, 2.0),
+            velocity: (speed * rad.cos(), speed * rad.sin()),
+        }
+    }
+
+    #[test]
+    fn head_on_pair_collides_when_the_gap_closes() {
+        // 26 m between the fronts, closing at 20 m/s.
+        let a = motion(1, (0.0, 0.0), 0.0, 10.0);
+        let b = motion(2, (30.0, 0.0), 180.0, 10.0);
+        let ttc = time_to_collision(&a, &b, 3.0).unwrap();
+        assert!(ttc > 1.3 - 1e-9 && ttc <= 1.3 + TTC_STEP + 1e-9, "TTC {}", ttc);
+        assert!((deceleration_to_avoid(&a, &b, ttc) - 20.0 / (2.0 * ttc)).abs() < 1e-9);
+        assert_eq!(time_to_collision(&a, &b, 1.0), None);
+    }
+
+    #[test]
+    fn vehicles_on_parallel_lanes_never_collide() {
+        let a = motion(1, (0.0, 0.0), 0.0, 10.0);
+        assert_eq!(time_to_collision(&a, &motion(2, (30.0, 3.5), 180.0, 10.0), 3.0), None);
+        assert_eq!(time_to_collision(&a, &motion(2, (5.0, 3.5), 0.0, 14.0), 3.0), None);
+    }
+
+    #[test]
+    fn post_encroachment_time_is_measured_once_the_second_vehicle_crosses() {
+        let mut monitor = SafetyMonitor::new(SeverityThresholds::default());
+        let dt = 1.0 / 60.0;
+        monitor.observe_occupancy(&motion(1, (0.0, 0.0), 90.0, 10.0), Approach::South, 10, dt);
+        // The vehicle behind from the same approach is not an encroachment.
+        monitor.observe_occupancy(&motion(3, (0.0, 0.0), 90.0, 10.0), Approach::South, 20, dt);
+        monitor.observe_occupancy(&motion(2, (0.0, 0.0), 180.0, 10.0), Approach::East, 50, dt);
+        let conflicts = monitor.finish(2);
+        assert_eq!(conflicts.len(), 1);
+        let (other, measures, severity) = conflicts[0];
+        assert_eq!(other, 3);
+        assert!((measures.pet.unwrap() - 0.5).abs() < 1e-9);
+        assert_eq!(severity, Severity::Serious);
+        assert!(monitor.finish(1).is_empty());
+    }
+
+    #[test]
+    fn thresholds_reject_duplicate_and_invalid_lines() {
+        assert!(SeverityThresholds::parse("serious 1 1 3\nserious 2 2 2\n").unwrap_err().contains("line 2"));
+        assert!(SeverityThresholds::parse("serious 1 1\n").is_err());
+        assert!(SeverityThresholds::parse("serious 1 -1 3\n").is_err());
+        assert!(SeverityThresholds::parse("serious 1 x 3\n").is_err());
+        assert!(SeverityThresholds::parse("fatal 1 1 3\n").is_err());
+        assert!(SeverityThresholds::parse("# nothing\n\n").is_err());
+        assert!(SeverityThresholds::parse("# comment\n\nslight 3 3 1\n").is_ok());
+    }
+
+    #[test]
+    fn classify_picks_the_most_severe_level_reached() {
+        // Given least severe first, the levels are still checked most severe first.
+        let thresholds = SeverityThresholds::parse("slight 3 3 1\nmoderate 2 2 2\nserious 1 1 3.4\n").unwrap();
+        let measures = |ttc, pet, drac| Measures { ttc, pet, drac };
+        assert_eq!(thresholds.classify(&measures(Some(2.5), None, Some(3.5))), Some(Severity::Serious));
+        assert_eq!(thresholds.classify(&measures(Some(1.5), Some(2.5), None)), Some(Severity::Moderate));
+        assert_eq!(thresholds.classify(&measures(None, Some(3.0), Some(0.5))), Some(Severity::Slight));
+        assert_eq!(thresholds.classify(&measures(Some(3.5), None, Some(0.5))), None);
+        assert_eq!(thresholds.classify(&Measures::default()), None);
+    }
+}
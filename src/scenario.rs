@@ -0,0 +1,152 @@
+use crate::road::{Approach, IntersectionGeometry, JunctionId, JunctionKind, LaneLayout, Movement};
+use crate::vehicle_class::VehicleMix;
+
+/// Layout of the simulated map: a grid of identical junctions.
+///
+/// A corridor is a grid with a single row, and the classic single intersection is a 1x1 grid.
+/// Neighbouring junctions are linked directly, so vehicles leaving one arrive at the next.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub rows: usize,
+    pub columns: usize,
+    /// Whether the junctions are crosses or roundabouts. Roundabouts have a single lane per approach,
+    /// see `LaneLayout::single`.
+    pub kind: JunctionKind,
+    /// Lanes of every junction's approaches.
+    pub lanes: LaneLayout,
+    /// Classes of the vehicles spawned at the edge of the map.
+    pub vehicle_mix: VehicleMix,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            rows: 1,
+            columns: 1,
+            kind: JunctionKind::default(),
+            lanes: LaneLayout::default(),
+            vehicle_mix: VehicleMix::default(),
+        }
+    }
+}
+
+impl Scenario {
+    /// Parses `single`, `corridor:N` (N junctions in a row) or `grid:RxC` (R rows of C junctions).
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid scenario: {} (expected single, corridor:N or grid:RxC)", text);
+        let count = |value: &str| match value.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(invalid()),
+        };
+        match text.split_once(':') {
+            None if text == "single" => Ok(Scenario::default()),
+            Some(("corridor", columns)) => Ok(Scenario { rows: 1, columns: count(columns)?, ..Scenario::default() }),
+            Some(("grid", size)) => {
+                let (rows, columns) = size.split_once('x').ok_or_else(invalid)?;
+                Ok(Scenario { rows: count(rows)?, columns: count(columns)?, ..Scenario::default() })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn junction_count(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Geometry of every junction, row by row. Junctions are spaced so that the road between two of
+    /// them is as long as the roads leading into the single intersection.
+    pub fn geometries(&self) -> Vec<IntersectionGeometry> {
+        let base = IntersectionGeometry::new(self.kind, self.lanes.clone());
+        let conflict_box = base.conflict_box();
+        let spacing_x = conflict_box.width() + base.horizontal_approach_length;
+        let spacing_y = conflict_box.height() + base.vertical_approach_length;
+        (0..self.junction_count())
+            .map(|junction| {
+                let (row, column) = (junction / self.columns, junction % self.columns);
+                IntersectionGeometry {
+                    center: (base.center.0 + spacing_x * column as f64, base.center.1 + spacing_y * row as f64),
+                    ..base.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Checks that missing legs of the lane layout face the edge of the map, so every junction is
+    /// linked to its neighbours. With more than one junction, approaches facing a leg also need a lane
+    /// for going straight: once a route has crossed as many junctions as there are, it goes straight
+    /// on lanes that allow it until it reaches the edge of the map.
+    pub fn validate(&self) -> Result<(), String> {
+        for side in Approach::ALL.into_iter().filter(|&side| !self.lanes.has_leg(side)) {
+            if (0..self.junction_count()).any(|junction| self.neighbour(junction, side).is_some()) {
+                return Err(format!("Junctions without a {} leg cannot have a neighbour on that side", side));
+            }
+        }
+        if self.junction_count() > 1 {
+            for approach in Approach::ALL.into_iter().filter(|&approach| self.lanes.has_leg(approach)) {
+                if self.lanes.has_leg(approach.opposite()) && !self.lanes.allows(approach, Movement::Straight) {
+                    let message = "needs a lane for going straight with more than one junction";
+                    return Err(format!("Approach {} {}", approach, message));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Junction on the other side of `side` of `junction`, if any.
+    pub fn neighbour(&self, junction: JunctionId, side: Approach) -> Option<JunctionId> {
+        let (row, column) = (junction / self.columns, junction % self.columns);
+        match side {
+            Approach::North if row > 0 => Some(junction - self.columns),
+            Approach::South if row + 1 < self.rows => Some(junction + self.columns),
+            Approach::West if column > 0 => Some(junction - 1),
+            Approach::East if column + 1 < self.columns => Some(junction + 1),
+            _ => None,
+        }
+    }
+
+    /// Width and height in meters of the area covered by the roads, which starts at the origin.
+    pub fn extent(&self) -> (f64, f64) {
+        let base = IntersectionGeometry::new(self.kind, self.lanes.clone());
+        let conflict_box = base.conflict_box();
+        (
+            self.columns as f64 * conflict_box.width() + (self.columns + 1) as f64 * base.horizontal_approach_length,
+            self.rows as f64 * conflict_box.height() + (self.rows + 1) as f64 * base.vertical_approach_length,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(text: &str, lanes: &str) -> Scenario {
+        Scenario { lanes: LaneLayout::parse(lanes).unwrap(), ..Scenario::parse(text).unwrap() }
+    }
+
+    #[test]
+    fn straight_lanes_are_required_only_with_more_than_one_junction() {
+        let message = "Approach North needs a lane for going straight with more than one junction";
+        for (text, lanes, expected) in [
+            ("single", "N:L,R", Ok(())),
+            ("corridor:2", "N:L,R", Err(message)),
+            ("grid:2x2", "N:L,R", Err(message)),
+            ("grid:2x2", "N:LS,R E:LS,R S:LS,R W:LS,R", Ok(())),
+            ("grid:3x3", "N:LSR E:LSR S:LSR W:LSR", Ok(())),
+        ] {
+            assert_eq!(scenario(text, lanes).validate(), expected.map_err(str::to_string), "{} with {}", text, lanes);
+        }
+    }
+
+    #[test]
+    fn missing_legs_must_face_the_edge_of_the_map() {
+        for (text, lanes, expected) in [
+            ("single", "S:-", Ok(())),
+            ("corridor:3", "N:-", Ok(())),
+            ("grid:1x3", "S:-", Ok(())),
+            ("grid:2x2", "S:-", Err("Junctions without a South leg cannot have a neighbour on that side")),
+            ("corridor:2", "W:-", Err("Junctions without a West leg cannot have a neighbour on that side")),
+        ] {
+            assert_eq!(scenario(text, lanes).validate(), expected.map_err(str::to_string), "{} with {}", text, lanes);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+/// Uniform grid over the plane for finding the items near a point without looking at every item.
+///
+/// Items are indices into a list the caller keeps, placed in the square cell their position falls in.
+/// Only occupied cells are stored, so the grid covers any network without knowing its size.
+pub struct SpatialGrid {
+    /// Side of a cell in meters.
+    cell_size: f64,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    /// A grid holding item `i` at the `i`-th of `positions`.
+    pub fn new(cell_size: f64, positions: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        let mut grid = SpatialGrid { cell_size, cells: HashMap::new() };
+        for (item, position) in positions.into_iter().enumerate() {
+            grid.insert(item, position);
+        }
+        grid
+    }
+
+    fn cell(&self, (x, y): (f64, f64)) -> (i32, i32) {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+
+    pub fn insert(&mut self, item: usize, position: (f64, f64)) {
+        self.cells.entry(self.cell(position)).or_default().push(item);
+    }
+
+    /// Removes `item`, which must have been inserted at `position`.
+    pub fn remove(&mut self, item: usize, position: (f64, f64)) {
+        let cell = self.cell(position);
+        if let Some(items) = self.cells.get_mut(&cell) {
+            items.retain(|&other| other != item);
+            if items.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Moves `item` from `from`, where it was inserted, to `to`.
+    pub fn relocate(&mut self, item: usize, from: (f64, f64), to: (f64, f64)) {
+        if self.cell(from) != self.cell(to) {
+            self.remove(item, from);
+            self.insert(item, to);
+        }
+    }
+
+    /// Items within `radius` of `center`, along with some of the items just beyond, in ascending order.
+    pub fn query(&self, center: (f64, f64), radius: f64) -> Vec<usize> {
+        let (min_x, min_y) = self.cell((center.0 - radius, center.1 - radius));
+        let (max_x, max_y) = self.cell((center.0 + radius, center.1 + radius));
+        let mut items: Vec<usize> = (min_x..=max_x)
+            .flat_map(|x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        items.sort_unstable();
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points spread over a 200 m square by a fixed linear congruential sequence.
+    fn scattered(count: usize) -> Vec<(f64, f64)> {
+        let mut state: u64 = 12345;
+        let mut next = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 200.0 - 100.0
+        };
+        (0..count).map(|_| (next(), next())).collect()
+    }
+
+    /// Checks that `query` finds every item a brute-force search does, and nothing further than a cell
+    /// diagonal beyond the radius.
+    fn assert_matches_brute_force(grid: &SpatialGrid, positions: &[(f64, f64)], cell_size: f64) {
+        let near = |center: (f64, f64), item: usize, radius: f64| {
+            (positions[item].0 - center.0).hypot(positions[item].1 - center.1) <= radius
+        };
+        for &center in positions {
+            for radius in [0.0, 5.0, 12.5, 40.0] {
+                let found = grid.query(center, radius);
+                let expected: Vec<usize> = (0..positions.len()).filter(|&i| near(center, i, radius)).collect();
+                assert!(expected.iter().all(|i| found.contains(i)), "missed neighbours of {:?}", center);
+                let reach = radius + 2.0 * cell_size * 2.0_f64.sqrt();
+                assert!(found.iter().all(|&i| near(center, i, reach)));
+                assert!(found.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn query_finds_the_same_neighbours_as_brute_force() {
+        let positions = scattered(300);
+        let grid = SpatialGrid::new(20.0, positions.iter().copied());
+        assert_matches_brute_force(&grid, &positions, 20.0);
+    }
+
+    #[test]
+    fn relocated_items_are_found_at_their_new_position() {
+        let mut positions = scattered(300);
+        let mut grid = SpatialGrid::new(20.0, positions.iter().copied());
+        let moves = scattered(600);
+        for (item, position) in positions.iter_mut().enumerate() {
+            let to = (position.0 + moves[item].0 / 4.0, position.1 + moves[item].1 / 4.0);
+            grid.relocate(item, *position, to);
+            *position = to;
+        }
+        assert_matches_brute_force(&grid, &positions, 20.0);
+    }
+}
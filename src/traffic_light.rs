@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::fs;
+
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::clock::SimClock;
+use crate::controller::{Command, IntersectionController};
+use crate::road::{Approach, IntersectionGeometry, LaneLayout, Movement};
+use crate::vehicle::Vehicle;
+use crate::view::View;
+
+/// Side of a drawn signal light in meters.
+const LIGHT_SIZE: f64 = 1.0;
+
+/// Plan used when `--signal-plan` is not given; the same as `config/signal_plan.txt`.
+const DEFAULT_PLAN: &str = "\
+N:LSR S:LSR 20 3 2
+E:LSR W:LSR 20 3 2
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightState {
+    Green,
+    Yellow,
+    Red,
+}
+
+/// One phase of a fixed-time plan: the movements that get green, and how long each stage lasts in seconds.
+#[derive(Clone, Debug)]
+pub struct Phase {
+    pub movements: HashSet<(Approach, Movement)>,
+    pub green: f64,
+    pub yellow: f64,
+    pub all_red: f64,
+}
+
+impl Phase {
+    fn duration(&self) -> f64 {
+        self.green + self.yellow + self.all_red
+    }
+}
+
+/// A cycle of phases that repeats for the whole run.
+///
+/// Plans are written one phase per line as a list of movement groups followed by the green, yellow
+/// and all-red times in seconds. A group is an approach letter (`N`, `E`, `S`, `W`) and the movements
+/// it gets (`L`, `S`, `R`), e.g. `N:SR S:SR 15 3 2`. Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug)]
+pub struct PhasePlan {
+    pub phases: Vec<Phase>,
+}
+
+impl PhasePlan {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read signal plan {}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut phases = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let phase = Self::parse_phase(line).map_err(|e| format!("Signal plan line {}: {}", number + 1, e))?;
+            phases.push(phase);
+        }
+        if phases.is_empty() {
+            return Err("Signal plan has no phases".to_string());
+        }
+        Ok(PhasePlan { phases })
+    }
+
+    fn parse_phase(line: &str) -> Result<Phase, String> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 4 {
+            return Err("expected movement groups followed by green, yellow and all-red times".to_string());
+        }
+        let (groups, times) = tokens.split_at(tokens.len() - 3);
+        let time = |token: &str| -> Result<f64, String> {
+            match token.parse::<f64>() {
+                Ok(value) if value >= 0.0 => Ok(value),
+                _ => Err(format!("invalid time: {}", token)),
+            }
+        };
+
+        let mut movements = HashSet::new();
+        for group in groups {
+            let (approach, letters) = group.split_once(':').ok_or_else(|| format!("invalid group: {}", group))?;
+            let approach = Approach::from_letter(approach).ok_or_else(|| format!("invalid approach: {}", approach))?;
+            for letter in letters.chars() {
+                let movement = Movement::from_letter(letter).ok_or_else(|| format!("invalid movement: {}", letter))?;
+                movements.insert((approach, movement));
+            }
+        }
+
+        let phase = Phase { movements, green: time(times[0])?, yellow: time(times[1])?, all_red: time(times[2])? };
+        if phase.duration() <= 0.0 {
+            return Err("phase has zero length".to_string());
+        }
+        Ok(phase)
+    }
+
+    /// The plan without the movements `lanes` has no lane for, skipping the phases left with none, so
+    /// a junction missing a leg does not wait through phases for it.
+    pub fn restricted_to(&self, lanes: &LaneLayout) -> Result<Self, String> {
+        let phases: Vec<Phase> = self.phases.iter()
+            .map(|phase| Phase {
+                movements: phase.movements.iter()
+                    .copied()
+                    .filter(|&(approach, movement)| lanes.allows(approach, movement))
+                    .collect(),
+                ..*phase
+            })
+            .filter(|phase| !phase.movements.is_empty())
+            .collect();
+        if phases.is_empty() {
+            return Err("Signal plan gives green to no movement of the junction".to_string());
+        }
+        Ok(PhasePlan { phases })
+    }
+
+    /// Light shown to `movement` on `approach` at `time` seconds into the run.
+    pub fn light(&self, approach: Approach, movement: Movement, time: f64) -> LightState {
+        let cycle: f64 = self.phases.iter().map(Phase::duration).sum();
+        let mut offset = time % cycle;
+        for phase in &self.phases {
+            if offset < phase.duration() {
+                if !phase.movements.contains(&(approach, movement)) {
+                    return LightState::Red;
+                }
+                return if offset < phase.green {
+                    LightState::Green
+                } else if offset < phase.green + phase.yellow {
+                    LightState::Yellow
+                } else {
+                    LightState::Red
+                };
+            }
+            offset -= phase.duration();
+        }
+        LightState::Red
+    }
+}
+
+impl Default for PhasePlan {
+    fn default() -> Self {
+        Self::parse(DEFAULT_PLAN).unwrap()
+    }
+}
+
+/// Fixed-time traffic light: vehicles facing a red light stop at their stop line, and so do vehicles
+/// facing a yellow light that can still stop comfortably.
+pub struct SignalController {
+    plan: PhasePlan,
+    geometry: IntersectionGeometry,
+    time: f64,
+}
+
+impl SignalController {
+    /// Runs `plan` at the junction laid out by `geometry`, ignoring movements it has no lane for.
+    pub fn new(plan: PhasePlan, geometry: &IntersectionGeometry) -> Result<Self, String> {
+        let plan = plan.restricted_to(&geometry.lanes)?;
+        Ok(SignalController { plan, geometry: geometry.clone(), time: 0.0 })
+    }
+
+    /// Center of the light for the `slot`-th movement of lane `lane_index` on `approach`, just past its
+    /// stop line. Lights of a lane shared by several movements are lined up one behind the other.
+    fn light_position(&self, approach: Approach, lane_index: u32, slot: usize) -> (f64, f64) {
+        let distance = self.geometry.approach_length(approach) + LIGHT_SIZE * (1.0 + 1.5 * slot as f64);
+        self.geometry.lane_point(approach, lane_index, distance)
+    }
+}
+
+impl IntersectionController for SignalController {
+    fn name(&self) -> &'static str {
+        "signal"
+    }
+
+    fn decide(&mut self, vehicles: &[&Vehicle], clock: &SimClock) -> Vec<Command> {
+        self.time = clock.now().as_secs_f64();
+        vehicles.iter()
+            .map(|vehicle| {
+                let distance = vehicle.distance_to_stop_line();
+                // Vehicles that already crossed the stop line clear the intersection whatever the light.
+                if distance < 0.0 || vehicle.is_in_intersection() || vehicle.is_leaving_intersection() {
+                    return Command::Proceed;
+                }
+                match self.plan.light(vehicle.junction_approach(), vehicle.junction_movement(), self.time) {
+                    LightState::Green => Command::Proceed,
+                    LightState::Yellow if vehicle.braking_distance() > distance => Command::Proceed,
+                    LightState::Yellow | LightState::Red => Command::StopWithin(distance),
+                }
+            })
+            .collect()
+    }
+
+    /// Pedestrians walk while no vehicle has green to drive straight over their crosswalk: every
+    /// movement from `side` and the straight movement leaving through it are red. Vehicles turning into
+    /// `side` on green yield to them.
+    fn may_cross(&self, side: Approach) -> bool {
+        Movement::ALL.iter().all(|&movement| self.plan.light(side, movement, self.time) == LightState::Red)
+            && self.plan.light(side.opposite(), Movement::Straight, self.time) == LightState::Red
+    }
+
+    fn draw(&self, canvas: &mut Canvas<Window>, view: &View) -> Result<(), String> {
+        for approach in Approach::ALL {
+            for (lane_index, movements) in (0..).zip(self.geometry.lanes.lanes(approach)) {
+                for (slot, &movement) in movements.iter().enumerate() {
+                    let color = match self.plan.light(approach, movement, self.time) {
+                        LightState::Green => sdl2::pixels::Color::RGB(0, 255, 0),
+                        LightState::Yellow => sdl2::pixels::Color::RGB(255, 200, 0),
+                        LightState::Red => sdl2::pixels::Color::RGB(255, 0, 0),
+                    };
+                    let (x, y) = self.light_position(approach, lane_index, slot);
+                    canvas.set_draw_color(color);
+                    canvas.fill_rect(view.centered_rect(x, y, LIGHT_SIZE, LIGHT_SIZE))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TICKS_PER_SECOND;
+
+    #[test]
+    fn bad_plans_are_rejected() {
+        for (text, error) in [
+            ("", "Signal plan has no phases"),
+            ("# only a comment\n\n", "Signal plan has no phases"),
+            ("N:S 10 3", "Signal plan line 1: expected movement groups followed by green, yellow and all-red times"),
+            ("N:S ten 3 2", "Signal plan line 1: invalid time: ten"),
+            ("N:S 10 -3 2", "Signal plan line 1: invalid time: -3"),
+            ("N:S 0 0 0", "Signal plan line 1: phase has zero length"),
+            ("N:S 10 3 2\nN:X 10 3 2", "Signal plan line 2: invalid movement: X"),
+            ("Q:S 10 3 2", "Signal plan line 1: invalid approach: Q"),
+            ("NS 10 3 2", "Signal plan line 1: invalid group: NS"),
+        ] {
+            assert_eq!(PhasePlan::parse(text).unwrap_err(), error, "plan {:?}", text);
+        }
+    }
+
+    #[test]
+    fn restricting_a_plan_to_no_movement_fails() {
+        let plan = PhasePlan::parse("N:L 10 3 2").unwrap();
+        let lanes = LaneLayout::parse("N:SR E:LSR S:LSR W:LSR").unwrap();
+        assert!(plan.restricted_to(&lanes).is_err());
+    }
+
+    #[test]
+    fn lights_follow_the_active_phase() {
+        // A 25 s cycle: north and south get 10 s green, 3 s yellow and 2 s all-red, then east 5 + 3 + 2.
+        let plan = PhasePlan::parse("N:SR S:S 10 3 2\nE:L 5 3 2").unwrap();
+        let north = |time| plan.light(Approach::North, Movement::Straight, time);
+        let east = |time| plan.light(Approach::East, Movement::Left, time);
+        for (time, north_light, east_light) in [
+            (0.0, LightState::Green, LightState::Red),
+            (9.9, LightState::Green, LightState::Red),
+            (10.0, LightState::Yellow, LightState::Red),
+            (12.9, LightState::Yellow, LightState::Red),
+            (13.0, LightState::Red, LightState::Red),
+            (15.0, LightState::Red, LightState::Green),
+            (20.0, LightState::Red, LightState::Yellow),
+            (23.0, LightState::Red, LightState::Red),
+            (25.0, LightState::Green, LightState::Red),
+            (70.0, LightState::Red, LightState::Yellow),
+        ] {
+            assert_eq!((north(time), east(time)), (north_light, east_light), "at {} s", time);
+        }
+        // Movements no phase gives green stay red.
+        assert_eq!(plan.light(Approach::North, Movement::Left, 0.0), LightState::Red);
+        assert_eq!(plan.light(Approach::West, Movement::Straight, 15.0), LightState::Red);
+    }
+
+    #[test]
+    fn lights_change_on_the_tick_their_stage_starts() {
+        let plan = PhasePlan::parse("N:S 1 0.5 0.5").unwrap();
+        let mut clock = SimClock::default();
+        let mut lights = Vec::new();
+        while clock.now().as_secs_f64() < 2.0 {
+            let light = plan.light(Approach::North, Movement::Straight, clock.now().as_secs_f64());
+            if lights.last().is_none_or(|&(_, last)| last != light) {
+                lights.push((clock.tick(), light));
+            }
+            clock.advance();
+        }
+        assert_eq!(
+            lights,
+            vec![
+                (0, LightState::Green),
+                (TICKS_PER_SECOND, LightState::Yellow),
+                (TICKS_PER_SECOND * 3 / 2, LightState::Red),
+            ]
+        );
+    }
+}
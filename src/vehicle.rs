@@ -1,192 +1,358 @@
-//use std::sync::atomic::{AtomicU32, Ordering};
 use std::f64::consts::PI;
-use rand::Rng;
+use std::rc::Rc;
+use std::time::Duration;
 
-use crate::road::{ROAD_HEIGHT};
+use crate::clock::SimClock;
+use crate::controller::Command;
+use crate::footprint::Footprint;
+use crate::road::{Approach, JunctionId, Lane, LaneId, LaneKind, Movement, RoadNetwork, CROSSWALK_WIDTH, STOP_LINE_WIDTH};
 use sdl2::rect::Point;
-use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-//static VEHICLE_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
+use crate::vehicle_class::VehicleClass;
+use crate::view::View;
 
-const VEHICLE_SIZE: u32 = 30;
-const VEHICLE_SPEED: f64 = 2.0;
-const SAFETY_DISTANCE: f64 = 35.0;
-const STOPPING_DISTANCE: f64 = 30.0; // Distance at which to start slowing down
-
-const NORTH_STOP_POS: f64 = 158.0;
-const SOUTH_STOP_POS: f64 = 440.0;
-const WEST_STOP_POS: f64 = 260.0;
-const EAST_STOP_POS: f64 = 540.0;
-
-#[derive(Clone, Copy, PartialEq)]
-pub enum Lane {
-    Middle = 0,
-    Right = 1,
-    Left = 2,
-}
+const APPROACH_BUFFER: f64 = 5.0;
+/// Gap in meters kept between a vehicle's front and the back of the vehicle ahead.
+pub const SAFETY_GAP: f64 = 0.5;
+/// Sideways slack in meters when deciding whether a vehicle is ahead in the same lane, for curves.
+const LANE_SLACK: f64 = 0.5;
+/// Speed in m/s at which a vehicle yielding to break a gridlock backs off.
+const BACK_OFF_SPEED: f64 = 1.0;
 
 #[derive(Clone)]
 pub struct Vehicle {
-    // pub id: u32,
+    /// Unique within a `World`, assigned in spawn order.
+    pub id: u32,
+    pub class: VehicleClass,
     pub x: f64,
     pub y: f64,
     pub angle: f64,
-    pub direction: u8,
-    pub lane: Lane,
+    /// Side the vehicle enters its first junction from.
+    pub approach: Approach,
+    /// Movement through its first junction.
+    pub movement: Movement,
+    /// Lane graph the vehicle drives on.
+    pub network: Rc<RoadNetwork>,
+    /// Lanes from the spawn point to the exit, in driving order.
+    pub route: Vec<LaneId>,
+    /// Lane the vehicle is on, one of `route`.
+    pub lane: LaneId,
+    /// Distance along `lane` from its start.
+    pub offset: f64,
+    /// Current speed in m/s.
+    pub speed: f64,
+    pub max_speed: f64,
+    pub max_acceleration: f64,
+    pub comfortable_deceleration: f64,
+    /// Distance driven since spawning, used for the average speed.
+    pub distance_travelled: f64,
+    pub ticks_alive: u64,
     pub color: sdl2::pixels::Color,
     pub border_color: sdl2::pixels::Color,
-    /// When a vehicle first enters the intersection, we record the time.
-    pub intersection_entry_time: Option<std::time::Instant>,
-    pub spawn_time: std::time::Instant,
-    pub close_call_count: u32,
-    pub is_in_collision: bool,
+    /// When a vehicle first enters the intersection, we record the simulation time.
+    pub intersection_entry_time: Option<Duration>,
+    /// Simulation time at which the vehicle was spawned.
+    pub spawn_time: Duration,
+    /// Ticks since the vehicle last moved.
+    pub stopped_ticks: u64,
+    /// Id of the vehicle this one stands still for: the one blocking its move, or the one it keeps its
+    /// gap to when that is closer than where the intersection controller asks it to stop.
+    pub waiting_for: Option<u32>,
+    /// Id of a vehicle this one keeps no gap to, given by the `World` to break a gridlock, until it is no
+    /// longer ahead of this one or within the safety gap.
+    pub priority_over: Option<u32>,
+    /// Id of the vehicle this one makes way for to break a gridlock: the one given priority over it, or
+    /// one making way in front of it. This one holds still, and backs off along its route while it
+    /// blocks that vehicle's move, until the `World` lets it go.
+    pub yielding_to: Option<u32>,
 }
 
 impl Vehicle {
-    /// Creates a new vehicle instance with specified starting position, direction and lane.
+    /// Creates a new vehicle at the start of the first lane of its route.
     ///
     /// # Arguments
-    /// * `x` - Initial x coordinate position
-    /// * `y` - Initial y coordinate position
-    /// * `direction` - Vehicle direction (0: North, 1: South, 2: West, 3: East)
-    /// * `lane` - Lane type (Middle: straight, Right: right turn, Left: left turn)
+    /// * `id` - Unique vehicle id
+    /// * `class` - Kind of vehicle, giving its footprint, acceleration and braking
+    /// * `approach` - Side of the intersection the vehicle enters from
+    /// * `movement` - Which way the vehicle turns through the intersection
+    /// * `network` - Lane graph the route runs on
+    /// * `route` - Lanes the vehicle follows, starting on an inbound lane used by `movement`
+    /// * `spawn_time` - Current simulation time
+    /// * `max_speed` - Top speed in m/s; the vehicle spawns driving at it
     ///
     /// # Returns
-    /// New Vehicle instance with color based on the lane:
+    /// New Vehicle instance with color based on the movement:
     /// - Yellow for right turns
-    /// - Cyan for straight (middle)
+    /// - Cyan for straight
     /// - Purple for left turns
-    pub fn new(x: i32, y: i32, direction: u8, lane: Lane) -> Self {
-        let color = match lane {
-            Lane::Right => sdl2::pixels::Color::RGB(255, 255, 0),   // Yellow
-            Lane::Middle => sdl2::pixels::Color::RGB(0, 255, 255),    // Cyan
-            Lane::Left => sdl2::pixels::Color::RGB(200, 150, 200),    // Purple
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: u32,
+        class: VehicleClass,
+        approach: Approach,
+        movement: Movement,
+        network: Rc<RoadNetwork>,
+        route: Vec<LaneId>,
+        spawn_time: Duration,
+        max_speed: f64,
+    ) -> Self {
+        let color = match movement {
+            Movement::Right => sdl2::pixels::Color::RGB(255, 255, 0),     // Yellow
+            Movement::Straight => sdl2::pixels::Color::RGB(0, 255, 255),  // Cyan
+            Movement::Left => sdl2::pixels::Color::RGB(200, 150, 200),    // Purple
         };
 
-        let init_angle = match direction {
-            0 => 270.0,     // North facing
-            1 => 90.0,      // South facing
-            2 => 180.0,     // West facing
-            3 => 0.0,       // East facing
-            _ => 0.0,
-        };
+        let lane = route[0];
+        let (x, y) = network.lane(lane).start();
 
         Vehicle {
-            // id: VEHICLE_ID_COUNTER.fetch_add(1, Ordering::SeqCst),
-            x: x as f64,
-            y: y as f64,
-            angle: init_angle,
-            direction,
+            id,
+            class,
+            x,
+            y,
+            angle: approach.heading_angle(),
+            approach,
+            movement,
+            network,
+            route,
             lane,
+            offset: 0.0,
+            speed: max_speed,
+            max_speed,
+            max_acceleration: class.max_acceleration(),
+            comfortable_deceleration: class.comfortable_deceleration(),
+            distance_travelled: 0.0,
+            ticks_alive: 0,
             color,
             border_color: sdl2::pixels::Color::RGB(0, 255, 0),
             intersection_entry_time: None,
-            spawn_time: std::time::Instant::now(),
-            close_call_count: 0,
-            is_in_collision: false,
+            spawn_time,
+            stopped_ticks: 0,
+            waiting_for: None,
+            priority_over: None,
+            yielding_to: None,
         }
     }
 
-    /// Determines if the given vehicle has priority to move into the intersection.
-    ///
-    /// A vehicle approaching the intersection (using a 50 unit buffer) must yield to any vehicle that
-    /// has already entered. Once in the intersection, the vehicle's recorded entry time determines its
-    /// priority.
-    pub fn check_intersection_priority(&self, vehicles: &[Vehicle]) -> bool {
-        let approaching_intersection = match self.direction {
-            0 => self.y <= 198.0 + 50.0 && self.y > 198.0, // North inbound
-            1 => self.y >= 406.0 - 50.0 && self.y < 406.0, // South inbound
-            2 => self.x <= 304.0 + 50.0 && self.x > 304.0, // West inbound
-            3 => self.x >= 502.0 - 50.0 && self.x < 502.0, // East inbound
-            _ => false,
-        };
+    /// Length of the vehicle's footprint in meters.
+    pub fn length(&self) -> f64 {
+        self.class.length()
+    }
+
+    /// Width of the vehicle's footprint in meters.
+    pub fn width(&self) -> f64 {
+        self.class.width()
+    }
+
+    /// Checks if the vehicle is on the crosswalk or inside the 5 m approach buffer in front of it.
+    pub fn is_approaching_intersection(&self) -> bool {
+        let distance = self.distance_to_intersection();
+        distance > 0.0 && distance - CROSSWALK_WIDTH <= APPROACH_BUFFER
+    }
+
+    /// Distance the vehicle still has to travel before its center reaches the end of the inbound lane
+    /// it is on or last drove on (negative once it has reached it).
+    pub fn distance_to_intersection(&self) -> f64 {
+        let index = self.route_index();
+        let inbound = self.inbound_index();
+        let passed: f64 = self.route[inbound..index].iter().map(|&id| self.network.lane(id).length()).sum();
+        self.network.lane(self.route[inbound]).length() - passed - self.offset
+    }
+
+    /// Distance the vehicle still has to travel before its front reaches the stop line in front of the
+    /// crosswalk at the end of the inbound lane it is on or last drove on (negative once it has crossed it).
+    pub fn distance_to_stop_line(&self) -> f64 {
+        self.distance_to_intersection() - CROSSWALK_WIDTH - STOP_LINE_WIDTH - self.length() / 2.0
+    }
+
+    fn current_lane(&self) -> &Lane {
+        self.network.lane(self.lane)
+    }
+
+    fn route_index(&self) -> usize {
+        self.route.iter().position(|&lane| lane == self.lane).unwrap_or(0)
+    }
+
+    /// Index in `route` of the inbound lane the vehicle is on or last drove on.
+    fn inbound_index(&self) -> usize {
+        let index = self.route_index();
+        (0..=index)
+            .rev()
+            .find(|&i| matches!(self.network.lane(self.route[i]).kind, LaneKind::Inbound { .. }))
+            .unwrap_or(0)
+    }
 
-        //println!("Approaching intersection: {}", approaching_intersection);
+    /// Junction the vehicle is driving towards or through; `None` once it is leaving the map. A vehicle
+    /// whose back is still in the junction it has just crossed belongs to that junction until it clears it.
+    pub fn junction(&self) -> Option<JunctionId> {
+        self.leaving_junction().or_else(|| self.current_lane().junction())
+    }
 
-        // The vehicle that is not yet near the intersection can continue normally.
-        if !approaching_intersection {
-            return true;
+    /// Junction whose connector the vehicle's center has left while its back is still in the junction.
+    fn leaving_junction(&self) -> Option<JunctionId> {
+        let index = self.route_index().checked_sub(1)?;
+        match self.network.lane(self.route[index]).kind {
+            LaneKind::Connector { junction, .. } if self.offset < self.length() / 2.0 => Some(junction),
+            _ => None,
         }
+    }
 
-        // If the vehicle is already in the intersection, it has priority.
-        if self.is_in_intersection() {
-            return true;
+    /// Side the vehicle enters its current junction from.
+    pub fn junction_approach(&self) -> Approach {
+        match self.network.lane(self.route[self.inbound_index()]).kind {
+            LaneKind::Inbound { approach, .. } => approach,
+            _ => self.approach,
         }
+    }
 
-        // Determine this vehicle's "entry time" (if it hasn't been set, use the current time).
-        let now = std::time::Instant::now();
-        let self_time = self.intersection_entry_time.unwrap_or(now);
+    /// Movement of the vehicle through its current junction.
+    pub fn junction_movement(&self) -> Movement {
+        match self.route.get(self.inbound_index() + 1).map(|&id| self.network.lane(id).kind) {
+            Some(LaneKind::Connector { movement, .. }) => movement,
+            _ => self.movement,
+        }
+    }
 
-        // If any other vehicle in the intersection has an earlier entry time, then this vehicle must wait.
-        for other in vehicles {
-            if std::ptr::eq(self, other) {
-                continue;
-            }
-            if other.is_in_intersection() {
-                if let Some(other_time) = other.intersection_entry_time {
-                    if other_time < self_time {
-                        return false;
-                    }
-                } else {
-                    // If the other vehicle is in the intersection but its time is not set,
-                    // assume it came in first.
-                    return false;
-                }
+    /// Lane and offset on it `distance` further along the route, or back along it for negative
+    /// distances; the first lane extends before its start and the last one past its end.
+    fn locate_ahead(&self, distance: f64) -> (LaneId, f64) {
+        let mut index = self.route_index();
+        let mut offset = self.offset + distance;
+        while offset < 0.0 && index > 0 {
+            index -= 1;
+            offset += self.network.lane(self.route[index]).length();
+        }
+        while index + 1 < self.route.len() {
+            let length = self.network.lane(self.route[index]).length();
+            if offset < length {
+                break;
             }
+            offset -= length;
+            index += 1;
         }
-        true
+        (self.route[index], offset)
     }
 
-    /// Updates vehicle position based on current state and surrounding vehicles.
-    ///
-    /// This method computes the next movement vector, checks collision, and then moves the vehicle if safe.
-    /// If the vehicle does not have priority to enter the intersection, it will not move and its border color
-    /// is set to orange.
-    pub fn update(&mut self, vehicles: &[Vehicle]) {
-        // Use the new intersection priority algorithm.
-        if !self.check_intersection_priority(vehicles) {
-            //self.border_color = sdl2::pixels::Color::RGB(255, 165, 0);
-            return;
-        }
+    /// Point `distance` further along the route, or back along it for negative distances.
+    pub fn point_ahead(&self, distance: f64) -> (f64, f64) {
+        let (lane, offset) = self.locate_ahead(distance);
+        self.network.lane(lane).path.point_at(offset)
+    }
+
+    /// Distance from the vehicle's center within which other vehicles' centers must lie to affect its
+    /// next move: its braking distance from top speed plus the safety gap, and half its length and
+    /// the longest class's length. Beyond it the vehicle ahead is too far to slow it down, and no
+    /// vehicle can be close enough to collide with.
+    pub fn interaction_radius(&self) -> f64 {
+        let longest = VehicleClass::ALL.iter().map(|class| class.length()).fold(0.0, f64::max);
+        self.max_speed * self.max_speed / (2.0 * self.comfortable_deceleration)
+            + SAFETY_GAP
+            + (self.length() + longest) / 2.0
+    }
 
-        // Check turning updates depending on lane and direction.
-        self.update_left_from_north(420.0, 277.0);
-        self.update_left_from_south(300.0, 200.0);
-        self.update_left_from_west(500.0, 200.0);
-        self.update_left_from_east(424.0, 320.0);
-        self.update_right_from_north(500.0, 400.0);
-        self.update_right_from_south(380.0, 325.0);
-        self.update_right_from_west(375.0, 280.0);
-        self.update_right_from_east(300.0, 400.0);
-
-        let (dx, dy) = self.get_movement_vector(vehicles);
-        let next_x = self.x + dx;
-        let next_y = self.y + dy;
-
-        if self.is_collision(next_x, next_y, vehicles) {
-            if !self.is_in_collision { // Increment only if not already in collision
-                self.close_call_count += 1;
-                //println!("Close call! {} at ({}, {})", self.close_call_count, self.x, self.y);
-                self.is_in_collision = true; // Set collision state to true
+    /// Updates vehicle speed and position based on current state and surrounding vehicles.
+    ///
+    /// The vehicle accelerates or brakes towards a target speed, limited by its maximum acceleration
+    /// and comfortable deceleration. The target is its cruise speed, lowered to keep a safe gap to the
+    /// vehicle ahead and to stop where the intersection controller asks it to. The vehicle then moves
+    /// along its path, facing the path's direction; if the next position would still collide, it stops
+    /// where it is. The vehicle advances by one tick of `clock`. A vehicle with `priority_over` set
+    /// keeps no gap to that vehicle, but still never moves into it; one with `yielding_to` set holds
+    /// still instead, backing off while it blocks that vehicle.
+    ///
+    /// `vehicles` must hold at least the other vehicles within `interaction_radius`.
+    pub fn update(&mut self, vehicles: &[&Vehicle], command: Command, clock: &SimClock) {
+        let dt = clock.dt();
+        self.ticks_alive += 1;
+
+        let all_vehicles = vehicles;
+        let others: Vec<&Vehicle>;
+        let vehicles = match self.priority_over {
+            Some(id) => {
+                others = vehicles.iter().copied().filter(|other| other.id != id).collect();
+                &others[..]
             }
-            //self.border_color = sdl2::pixels::Color::RGB(255, 0, 0);
+            None => vehicles,
+        };
+
+        let target_speed = self.target_speed(vehicles, command, dt);
+        self.speed = if target_speed > self.speed {
+            (self.speed + self.max_acceleration * dt).min(target_speed)
         } else {
-            self.is_in_collision = false; // Reset collision state to false
+            (self.speed - self.comfortable_deceleration * dt).max(target_speed)
+        };
+        // Never drive past a point the controller asked to stop at, even if that means braking hard.
+        if let Command::StopWithin(distance) = command {
+            self.speed = self.speed.min(distance.max(0.0) / dt);
+        }
+        let backing_off = self.yielding_to.is_some_and(|id| {
+            all_vehicles.iter().any(|other| other.id == id && other.waiting_for == Some(self.id))
+        });
+        if self.yielding_to.is_some() {
+            self.speed = 0.0;
         }
 
-        if self.can_move(next_x, next_y, vehicles) {
+        let travel = if backing_off { -BACK_OFF_SPEED * dt } else { self.speed * dt };
+        let (next_lane, next_offset) = self.locate_ahead(travel);
+        let (next_x, next_y) = self.network.lane(next_lane).path.point_at(next_offset);
+        let next_angle = self.network.lane(next_lane).path.heading_at(next_offset);
+        let next = Footprint::new((next_x, next_y), next_angle, self.length(), self.width());
+
+        let blocker = if backing_off {
+            // Backing off stops at the start of the route and at vehicles behind.
+            let current = self.footprint();
+            all_vehicles.iter()
+                .find(|other| {
+                    let footprint = other.footprint();
+                    other.id != self.id && next.overlaps(&footprint) && !current.overlaps(&footprint)
+                })
+                .map(|other| other.id)
+                .or(self.yielding_to.filter(|_| next_offset < 0.0))
+        } else {
+            self.is_collision(next, all_vehicles)
+        };
+        let moved = blocker.is_none() && travel != 0.0;
+        if blocker.is_none() {
             self.x = next_x;
             self.y = next_y;
+            self.angle = next_angle;
+            self.lane = next_lane;
+            self.offset = next_offset;
+            self.distance_travelled += self.speed * dt;
             self.border_color = sdl2::pixels::Color::RGB(0, 255, 0);
+        } else {
+            self.speed = 0.0;
+        }
+
+        self.stopped_ticks = if moved { 0 } else { self.stopped_ticks + 1 };
+        self.waiting_for = if self.speed > 0.0 {
+            None
+        } else {
+            let room = match command {
+                Command::StopWithin(distance) => distance,
+                Command::Proceed => f64::INFINITY,
+            };
+            blocker.or(self.yielding_to).or_else(|| {
+                self.gap_to_leader(vehicles).filter(|&(gap, _)| gap - SAFETY_GAP <= room).map(|(_, id)| id)
+            })
+        };
+        if let Some(id) = self.priority_over {
+            let footprint = self.footprint().inflated(SAFETY_GAP);
+            let still_in_the_way = all_vehicles.iter()
+                .find(|other| other.id == id)
+                .is_some_and(|other| self.is_ahead(other) || footprint.overlaps(&other.footprint()));
+            if !still_in_the_way {
+                self.priority_over = None;
+            }
         }
 
         // When a vehicle enters the intersection, record its entry time once.
         if self.is_in_intersection() {
-            //println!("Vehicle {} entered intersection at {:?}", self.id, std::time::Instant::now());
+            //println!("Vehicle {} entered intersection at {:?}", self.id, now);
             if self.intersection_entry_time.is_none() {
-                self.intersection_entry_time = Some(std::time::Instant::now());
+                self.intersection_entry_time = Some(clock.now());
             }
         } else {
             // Reset the entry time once outside the intersection.
@@ -194,223 +360,178 @@ impl Vehicle {
         }
     }
 
-    fn update_left_from_north(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Middle || self.direction != 0 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 180.0;
-        }
+    /// Predicts the points the vehicle will pass over the next `length` meters of its route,
+    /// one every `step` meters.
+    pub fn predicted_path(&self, step: f64, length: f64) -> Vec<(f64, f64)> {
+        (1..=(length / step).ceil() as usize)
+            .map(|i| self.point_ahead(step * i as f64))
+            .collect()
     }
 
-    fn update_left_from_south(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Middle || self.direction != 1 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 180.0;
-        }
+    /// Speed the vehicle drives at when nothing is in its way: its top speed, capped by the speed limit
+    /// of its lane.
+    pub fn cruise_speed(&self) -> f64 {
+        self.max_speed.min(self.current_lane().speed_limit)
     }
 
-    fn update_left_from_west(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Middle || self.direction != 2 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 270.0;
+    /// Speed the vehicle crosses its current junction at when nothing is in its way.
+    pub fn junction_speed(&self) -> f64 {
+        match self.route.get(self.inbound_index() + 1) {
+            Some(&id) => self.max_speed.min(self.network.lane(id).speed_limit),
+            None => self.max_speed,
         }
     }
 
-    fn update_left_from_east(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Middle || self.direction != 3 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = -90.0;
-        }
+    /// Highest speed the vehicle can drive at for the next `dt` seconds and still stop within `distance`
+    /// at comfortable deceleration afterwards.
+    pub fn stopping_speed(&self, distance: f64, dt: f64) -> f64 {
+        let deceleration = self.comfortable_deceleration;
+        deceleration * ((dt * dt + 2.0 * distance.max(0.0) / deceleration).sqrt() - dt)
     }
 
-    fn update_right_from_north(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Left || self.direction != 0 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 0.0;
-        }
+    /// Distance the vehicle needs to stop from its current speed at comfortable deceleration.
+    pub fn braking_distance(&self) -> f64 {
+        self.speed * self.speed / (2.0 * self.comfortable_deceleration)
     }
 
-    fn update_right_from_south(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Left || self.direction != 1 {
-            return;
+    fn target_speed(&self, vehicles: &[&Vehicle], command: Command, dt: f64) -> f64 {
+        let mut target = self.cruise_speed();
+        if let Command::StopWithin(distance) = command {
+            target = target.min(self.stopping_speed(distance, dt));
         }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 0.0;
+        if let Some((gap, _)) = self.gap_to_leader(vehicles) {
+            target = target.min(self.stopping_speed(gap - SAFETY_GAP, dt));
         }
+        target
     }
 
-    fn update_right_from_west(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Left || self.direction != 2 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 90.0;
-        }
+    /// Gap between the vehicle's front and the back of the closest vehicle straight ahead, if any, and
+    /// that vehicle's id. Vehicles beyond the interaction radius are left out, as they cannot slow it
+    /// down: whether they are among `vehicles` must not change what the vehicle waits for.
+    fn gap_to_leader(&self, vehicles: &[&Vehicle]) -> Option<(f64, u32)> {
+        let radius = self.interaction_radius();
+        vehicles.iter()
+            .filter(|other| other.id != self.id && (other.x - self.x).hypot(other.y - self.y) <= radius)
+            .filter(|other| self.is_following(other))
+            .map(|other| (self.relative_position(other).0 - (self.length() + other.length()) / 2.0, other.id))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
     }
 
-    fn update_right_from_east(&mut self, target_x: f64, target_y: f64) {
-        if self.lane != Lane::Left || self.direction != 3 {
-            return;
-        }
-        if (self.x - target_x).abs() < 5.0 && (self.y - target_y).abs() < 5.0 {
-            self.angle = 90.0;
-        }
+    /// Checks if the vehicle follows `other`: `other` is straight ahead of it, close enough to its line
+    /// to keep a gap to. Of two vehicles crossing with each in front of the other, the younger follows.
+    fn is_following(&self, other: &Vehicle) -> bool {
+        self.is_ahead(other) && !(other.is_ahead(self) && self.id < other.id)
     }
 
-    /// Calculates movement vector based on current angle and velocity.
-    ///
-    /// Uses the current velocity—which may be reduced when near the intersection—to compute dx and dy.
-    fn get_movement_vector(&mut self, vehicles: &[Vehicle]) -> (f64, f64) {
-        let current_speed = self.get_velocity(vehicles);
+    /// Checks if `other` is in front of the vehicle and within a lane's slack of its line.
+    fn is_ahead(&self, other: &Vehicle) -> bool {
+        let (along, across) = self.relative_position(other);
+        along > 0.0 && across < (self.width() + other.width()) / 2.0 + LANE_SLACK
+    }
+
+    /// Position of `other` relative to this vehicle: distance along its heading and distance across it.
+    fn relative_position(&self, other: &Vehicle) -> (f64, f64) {
         let rad = self.angle * PI / 180.0;
-        let dx = current_speed * rad.cos();
-        let dy = current_speed * rad.sin();
-        (dx, dy)
+        let (heading_x, heading_y) = (rad.cos(), rad.sin());
+        let (dx, dy) = (other.x - self.x, other.y - self.y);
+        (dx * heading_x + dy * heading_y, (dx * heading_y - dy * heading_x).abs())
     }
 
-    /// Returns the current vehicle velocity.
-    ///
-    /// When approaching an intersection, the vehicle slows down (30% speed); within the intersection,
-    /// a randomized speed multiplier is applied.
-    pub fn get_velocity(&self, _vehicles: &[Vehicle]) -> f64 {
-        let slow_down_factor = 0.3;
-        let approach_buffer = 50.0;
-        let rate = rand::thread_rng().gen_range(1.55..=3.95);
-        let should_slow_down = match self.direction {
-            2 => self.x <= 304.0 + approach_buffer && self.x > 304.0,
-            3 => self.x >= 502.0 - approach_buffer && self.x < 502.0,
-            0 => self.y <= 198.0 + approach_buffer && self.y > 198.0,
-            1 => self.y >= 406.0 - approach_buffer && self.y < 406.0,
-            _ => false,
-        };
-        let base_speed = if self.is_in_intersection() {
-            VEHICLE_SPEED * rand::thread_rng().gen_range(1.55..=2.55)
-        } else {
-            VEHICLE_SPEED * rate
-        };
-        if should_slow_down {
-            base_speed * slow_down_factor
-        } else {
-            base_speed
-        }
+    /// Velocity in m/s along the vehicle's heading.
+    pub fn velocity(&self) -> (f64, f64) {
+        let rad = self.angle * PI / 180.0;
+        (self.speed * rad.cos(), self.speed * rad.sin())
     }
 
-    /// Determines if the vehicle can move safely to the next position.
-    ///
-    /// Checks for imminent collisions or blocking positions (stop positions).
-    fn can_move(&mut self, next_x: f64, next_y: f64, vehicles: &[Vehicle]) -> bool {
-        if self.is_collision(next_x, next_y, vehicles) {
-            return false;
-        }
+    /// Ground the vehicle covers.
+    pub fn footprint(&self) -> Footprint {
+        Footprint::new((self.x, self.y), self.angle, self.length(), self.width())
+    }
 
-        // First count vehicles in intersection
-        let vehicles_in_intersection = vehicles.iter()
-            .filter(|v| v.is_in_intersection())
-            .count();
+    /// Checks if the point `(x, y)` is within `margin` of the vehicle's footprint.
+    pub fn is_near(&self, x: f64, y: f64, margin: f64) -> bool {
+        self.footprint().inflated(margin).contains((x, y))
+    }
 
-        for other in vehicles {
-            if vehicles_in_intersection >= 3 && !self.is_in_intersection() {
-                if next_x == WEST_STOP_POS && other.direction == 3 {
-                    return false;
-                } else if next_x == EAST_STOP_POS && other.direction == 2 {
-                    return false;
-                } else if next_y == SOUTH_STOP_POS && other.direction == 0 {
-                    return false;
-                } else if next_y == NORTH_STOP_POS && other.direction == 1 {
-                    return false;
-                }
-            }
+    /// Returns the current vehicle velocity in m/s.
+    pub fn get_velocity(&self) -> f64 {
+        self.speed
+    }
+
+    /// Average speed since spawning in m/s, given the length `dt` of a tick in seconds.
+    pub fn average_velocity(&self, dt: f64) -> f64 {
+        if self.ticks_alive == 0 {
+            self.speed
+        } else {
+            self.distance_travelled / (self.ticks_alive as f64 * dt)
         }
-        true
     }
 
-    /// Checks for potential collisions with other vehicles.
+    /// Checks for potential collisions with other vehicles, were the vehicle to move to `next`, and
+    /// returns the vehicle it would collide with.
     ///
-    /// If another vehicle is within the safety or stopping distance ahead, a collision is assumed.
-    fn is_collision(&mut self, next_x: f64, next_y: f64, vehicles: &[Vehicle]) -> bool {
-        for other in vehicles {
-            if std::ptr::eq(self, other) {
-                continue;
-            }
-            let dx = next_x - other.x;
-            let dy = next_y - other.y;
-            let distance = (dx * dx + dy * dy).sqrt();
-
-            let is_ahead = match self.direction {
-                0 => other.y < self.y && (other.x - self.x).abs() < VEHICLE_SIZE as f64,
-                1 => other.y > self.y && (other.x - self.x).abs() < VEHICLE_SIZE as f64,
-                2 => other.x < self.x && (other.y - self.y).abs() < VEHICLE_SIZE as f64,
-                3 => other.x > self.x && (other.y - self.y).abs() < VEHICLE_SIZE as f64,
-                _ => false,
-            };
-
-            // Check if vehicles are moving in the same direction
-            let same_direction = self.direction == other.direction;
-
-            // Only count close calls for vehicles moving in different directions
-            if !same_direction && distance < SAFETY_DISTANCE {
-                if !self.is_in_collision { // Increment only if not already in collision
-                    self.close_call_count += 1;
-                    //println!("Close call! {} at ({}, {})", self.close_call_count, self.x, self.y);
-                    self.is_in_collision = true; // Set collision state to true
+    /// A collision is assumed if `next` overlaps another vehicle it does not overlap yet, or overlaps it
+    /// more closely; nothing waives that. The move is also held back if it brings the two closer with
+    /// `next` lengthened by the safety gap at the front for the vehicle it follows, unless the older of
+    /// two vehicles contesting that gap goes first or the vehicle has priority over the one it follows.
+    /// Holding at the stop line is up to the intersection controller.
+    fn is_collision(&self, next: Footprint, vehicles: &[&Vehicle]) -> Option<u32> {
+        let safety_zone = next.extended_forward(SAFETY_GAP);
+        let current = self.footprint();
+        vehicles.iter()
+            .filter(|other| other.id != self.id)
+            .find(|other| {
+                let footprint = other.footprint();
+                let distance = |(x, y): (f64, f64)| (other.x - x).hypot(other.y - y);
+                let closer = distance(next.center) < distance(current.center);
+                if next.overlaps(&footprint) && (closer || !current.overlaps(&footprint)) {
+                    return true;
                 }
-            }
-
-            if !same_direction && distance < STOPPING_DISTANCE {
-                return true;
-            }
-
-            if is_ahead && distance < SAFETY_DISTANCE {
-                return true;
-            }
-            if distance < STOPPING_DISTANCE {
-                return true;
-            }
-        }
-        false
+                // The safety gap is kept to vehicles ahead in the same band only: two vehicles crossing
+                // each other's path can both have the other in front of them, and would wait for ever.
+                // Vehicles following this one keep their own gap to it, so they never hold it back.
+                let ahead = self.is_following(other);
+                if !ahead || self.priority_over == Some(other.id) {
+                    return false;
+                }
+                // Two vehicles that are already within the gap, or both nosing into each other's gap,
+                // would hold each other back for ever, so the older one may close the gap first.
+                let contested = current.extended_forward(SAFETY_GAP).overlaps(&footprint)
+                    || footprint.extended_forward(SAFETY_GAP).overlaps(&current);
+                safety_zone.overlaps(&footprint) && closer && !(contested && self.id < other.id)
+            })
+            .map(|other| other.id)
     }
 
-    /// Checks if the vehicle is within the intersection boundaries.
-    ///
-    /// The intersection is defined as the rectangle bounded by [304, 502] in the horizontal
-    /// and [198, 406] in the vertical direction.
+    /// Checks if the vehicle is on a connector through the junction.
     pub fn is_in_intersection(&self) -> bool {
-        let intersection_left = 304.0;
-        let intersection_right = 502.0;
-        let intersection_top = 198.0;
-        let intersection_bottom = 406.0;
+        self.current_lane().is_connector()
+    }
 
-        self.x > intersection_left &&
-        self.x < intersection_right &&
-        self.y > intersection_top &&
-        self.y < intersection_bottom
+    /// Checks if the vehicle's center has left the junction but its back has not.
+    pub fn is_leaving_intersection(&self) -> bool {
+        self.leaving_junction().is_some()
     }
 
-    /// Checks if the vehicle has completed its journey (i.e. left the simulation bounds).
+    /// Checks if any part of the vehicle's footprint is in the junction: its front has driven in and its
+    /// back has not left yet.
+    pub fn occupies_intersection(&self) -> bool {
+        let entering = matches!(self.current_lane().kind, LaneKind::Inbound { .. })
+            && self.distance_to_intersection() < self.length() / 2.0;
+        entering || self.is_in_intersection() || self.is_leaving_intersection()
+    }
+
+    /// Checks if the vehicle has completed its journey (i.e. reached the end of its route).
     pub fn is_finished(&self) -> bool {
-        self.x < 0.0 || self.x > ROAD_HEIGHT as f64 || self.y < 0.0 || self.y > ROAD_HEIGHT as f64
+        self.route_index() + 1 == self.route.len() && self.offset >= self.current_lane().length()
     }
 
     /// Renders the vehicle on the canvas.
     ///
     /// Draws the vehicle rectangle with its color, border, and a direction arrow indicating
-    /// the current velocity vector.
-    pub fn draw(&self, canvas: &mut Canvas<Window>, texture: &sdl2::render::Texture) -> Result<(), String> {
-        let rect = Rect::new(
-            self.x as i32 - VEHICLE_SIZE as i32 / 2,
-            self.y as i32 - VEHICLE_SIZE as i32 / 2,
-            VEHICLE_SIZE,
-            VEHICLE_SIZE,
-        );
+    /// the current velocity vector. `view` maps the vehicle's position onto the window.
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture: &sdl2::render::Texture, view: &View) -> Result<(), String> {
+        let rect = view.centered_rect(self.x, self.y, self.length(), self.width());
 
         // Create destination rectangle for the texture
         canvas.copy_ex(
@@ -433,3 +554,57 @@ impl Vehicle {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::road::IntersectionGeometry;
+
+    /// A vehicle of `class` standing at the start of the straight-through lane from the west of a
+    /// single junction.
+    fn standing(class: VehicleClass) -> Vehicle {
+        let geometry = IntersectionGeometry::default();
+        let network = Rc::new(RoadNetwork::new(std::slice::from_ref(&geometry), |_, _| None));
+        let start = network.inbound_lanes(0, Approach::West, Movement::Straight)[0];
+        let route = network
+            .route(start, 1, |next| {
+                *next.iter()
+                    .find(|&&lane| network.lane(lane).kind == LaneKind::Connector { junction: 0, movement: Movement::Straight })
+                    .unwrap()
+            })
+            .unwrap();
+        let max_speed = class.top_speed_range().1;
+        let mut vehicle =
+            Vehicle::new(1, class, Approach::West, Movement::Straight, network, route, Duration::ZERO, max_speed);
+        vehicle.speed = 0.0;
+        vehicle
+    }
+
+    #[test]
+    fn speed_changes_within_the_class_limits() {
+        for class in VehicleClass::ALL {
+            let mut vehicle = standing(class);
+            let mut clock = SimClock::default();
+            let dt = clock.dt();
+            // Drive off for a few seconds, then stop a little further away than the braking distance.
+            let mut stop_at = None;
+            while stop_at.is_none() || vehicle.speed > 0.0 {
+                if stop_at.is_none() && clock.now() >= Duration::from_secs(6) {
+                    stop_at = Some(vehicle.distance_travelled + vehicle.braking_distance() + 2.0);
+                }
+                let command = match stop_at {
+                    Some(stop_at) => Command::StopWithin(stop_at - vehicle.distance_travelled),
+                    None => Command::Proceed,
+                };
+                let speed = vehicle.speed;
+                vehicle.update(&[], command, &clock);
+                clock.advance();
+                assert!(vehicle.speed - speed <= class.max_acceleration() * dt + 1e-9, "{} accelerates too hard", class);
+                assert!(speed - vehicle.speed <= class.comfortable_deceleration() * dt + 1e-9, "{} brakes too hard", class);
+                assert!(vehicle.speed <= vehicle.max_speed, "{} drives too fast", class);
+                assert!(clock.now() < Duration::from_secs(30), "{} does not stop", class);
+            }
+            assert!(vehicle.distance_travelled <= stop_at.unwrap() + 1e-6, "{} stops too late", class);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+
+use rand::Rng;
+
+/// Kind of vehicle, giving its footprint, performance and sprite.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VehicleClass {
+    Car,
+    Truck,
+    Bus,
+    Motorcycle,
+}
+
+impl VehicleClass {
+    pub const ALL: [VehicleClass; 4] =
+        [VehicleClass::Car, VehicleClass::Truck, VehicleClass::Bus, VehicleClass::Motorcycle];
+
+    /// Position in `ALL`, used to index per-class tables such as the loaded sprites.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Parses the name used on the command line: `car`, `truck`, `bus` or `motorcycle`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        VehicleClass::ALL.into_iter()
+            .find(|class| class.name() == name)
+            .ok_or_else(|| format!("Invalid vehicle class: {} (expected car, truck, bus or motorcycle)", name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VehicleClass::Car => "car",
+            VehicleClass::Truck => "truck",
+            VehicleClass::Bus => "bus",
+            VehicleClass::Motorcycle => "motorcycle",
+        }
+    }
+
+    /// Length of the footprint in meters, along the direction of travel.
+    pub fn length(self) -> f64 {
+        match self {
+            VehicleClass::Car => 4.0,
+            VehicleClass::Truck => 8.0,
+            VehicleClass::Bus => 10.0,
+            VehicleClass::Motorcycle => 2.0,
+        }
+    }
+
+    /// Width of the footprint in meters.
+    pub fn width(self) -> f64 {
+        match self {
+            VehicleClass::Car => 1.8,
+            VehicleClass::Truck | VehicleClass::Bus => 2.5,
+            VehicleClass::Motorcycle => 0.8,
+        }
+    }
+
+    /// Range top speeds are drawn from when vehicles spawn, in m/s. Lane speed limits still apply.
+    pub fn top_speed_range(self) -> (f64, f64) {
+        match self {
+            VehicleClass::Car => (7.5, 10.5),
+            VehicleClass::Truck => (6.0, 8.5),
+            VehicleClass::Bus => (6.5, 9.0),
+            VehicleClass::Motorcycle => (8.5, 11.5),
+        }
+    }
+
+    /// Maximum acceleration in m/s².
+    pub fn max_acceleration(self) -> f64 {
+        match self {
+            VehicleClass::Car => 2.5,
+            VehicleClass::Truck => 1.2,
+            VehicleClass::Bus => 1.5,
+            VehicleClass::Motorcycle => 3.5,
+        }
+    }
+
+    /// Deceleration the driver is comfortable braking at, in m/s².
+    pub fn comfortable_deceleration(self) -> f64 {
+        match self {
+            VehicleClass::Car => 4.0,
+            VehicleClass::Truck | VehicleClass::Bus => 3.0,
+            VehicleClass::Motorcycle => 5.0,
+        }
+    }
+
+    /// Path of the sprite drawn for the class, facing east.
+    pub fn sprite(self) -> &'static str {
+        match self {
+            VehicleClass::Car => "./src/assets/sprite.png",
+            VehicleClass::Truck => "./src/assets/truck.png",
+            VehicleClass::Bus => "./src/assets/bus.png",
+            VehicleClass::Motorcycle => "./src/assets/motorcycle.png",
+        }
+    }
+}
+
+impl fmt::Display for VehicleClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Share of each vehicle class in the spawned traffic.
+///
+/// Mixes are written as comma separated `class:weight` pairs, e.g. `car:70,truck:10,bus:5,motorcycle:15`.
+/// Weights are relative, and classes left out are never spawned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VehicleMix {
+    /// Weight of each class, indexed like `VehicleClass::ALL`.
+    weights: [f64; 4],
+}
+
+impl Default for VehicleMix {
+    /// Cars only.
+    fn default() -> Self {
+        VehicleMix { weights: [1.0, 0.0, 0.0, 0.0] }
+    }
+}
+
+impl VehicleMix {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut weights = [0.0; 4];
+        for entry in text.split(',').map(str::trim) {
+            let (name, weight) = entry.split_once(':')
+                .ok_or_else(|| format!("Invalid vehicle mix entry: {} (expected class:weight)", entry))?;
+            let class = VehicleClass::parse(name)?;
+            weights[class.index()] = match weight.parse::<f64>() {
+                Ok(weight) if weight >= 0.0 && weight.is_finite() => weight,
+                _ => return Err(format!("Invalid weight for {}: {}", class, weight)),
+            };
+        }
+        if weights.iter().sum::<f64>() <= 0.0 {
+            return Err("Vehicle mix needs a class with a positive weight".to_string());
+        }
+        Ok(VehicleMix { weights })
+    }
+
+    /// Draws a class according to the weights.
+    pub fn pick(&self, rng: &mut impl Rng) -> VehicleClass {
+        let mut remaining = rng.gen_range(0.0..self.weights.iter().sum::<f64>());
+        for class in VehicleClass::ALL {
+            if remaining < self.weights[class.index()] {
+                return class;
+            }
+            remaining -= self.weights[class.index()];
+        }
+        // Only reached through rounding; fall back to the last class in the mix.
+        VehicleClass::ALL.into_iter().rev().find(|class| self.weights[class.index()] > 0.0).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn bad_mixes_are_rejected() {
+        for (text, error) in [
+            ("van:1", "Invalid vehicle class: van (expected car, truck, bus or motorcycle)"),
+            ("car", "Invalid vehicle mix entry: car (expected class:weight)"),
+            ("car:-1", "Invalid weight for car: -1"),
+            ("car:many", "Invalid weight for car: many"),
+            ("car:inf", "Invalid weight for car: inf"),
+            ("car:0", "Vehicle mix needs a class with a positive weight"),
+            ("car:0,truck:0,bus:0,motorcycle:0", "Vehicle mix needs a class with a positive weight"),
+        ] {
+            assert_eq!(VehicleMix::parse(text).unwrap_err(), error, "mix {:?}", text);
+        }
+    }
+
+    #[test]
+    fn picks_follow_the_weights_and_the_seed() {
+        let mix = VehicleMix::parse("car:70, truck:0, bus:10,motorcycle:20").unwrap();
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10_000).map(|_| mix.pick(&mut rng)).collect::<Vec<_>>()
+        };
+        let first = picks(7);
+        assert_eq!(first, picks(7));
+        assert_ne!(first, picks(8));
+
+        let share = |class| first.iter().filter(|&&picked| picked == class).count() as f64 / first.len() as f64;
+        assert_eq!(share(VehicleClass::Truck), 0.0);
+        for (class, weight) in [(VehicleClass::Car, 0.7), (VehicleClass::Bus, 0.1), (VehicleClass::Motorcycle, 0.2)] {
+            assert!((share(class) - weight).abs() < 0.02, "{} picked {} of the time", class, share(class));
+        }
+    }
+}
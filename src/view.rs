@@ -0,0 +1,55 @@
+use sdl2::rect::Rect;
+
+/// Maps world coordinates, in meters, onto window pixels.
+///
+/// The simulation never looks at the view, so resizing the window only changes how big the world is
+/// drawn, not how it behaves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Pixels per meter.
+    scale: f64,
+    /// Window position of the world origin, in pixels.
+    origin: (f64, f64),
+}
+
+impl View {
+    /// Fits a world of `extent` meters, starting at the origin, into a window of `window` pixels,
+    /// keeping its aspect ratio and centering it.
+    pub fn fit(extent: (f64, f64), window: (u32, u32)) -> Self {
+        let (width, height) = (window.0 as f64, window.1 as f64);
+        let scale = (width / extent.0).min(height / extent.1);
+        View {
+            scale,
+            origin: ((width - extent.0 * scale) / 2.0, (height - extent.1 * scale) / 2.0),
+        }
+    }
+
+    /// Pixels per meter.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Window position of a world point.
+    pub fn point(&self, x: f64, y: f64) -> (i32, i32) {
+        (
+            (self.origin.0 + x * self.scale).round() as i32,
+            (self.origin.1 + y * self.scale).round() as i32,
+        )
+    }
+
+    /// Number of pixels a world length covers, at least one so thin features stay visible.
+    pub fn length(&self, meters: f64) -> u32 {
+        ((meters * self.scale).round() as u32).max(1)
+    }
+
+    /// Window rectangle of the world rectangle with its top left corner at `(x, y)`.
+    pub fn rect(&self, x: f64, y: f64, width: f64, height: f64) -> Rect {
+        let (left, top) = self.point(x, y);
+        Rect::new(left, top, self.length(width), self.length(height))
+    }
+
+    /// Window rectangle of the world rectangle centered on `(x, y)`.
+    pub fn centered_rect(&self, x: f64, y: f64, width: f64, height: f64) -> Rect {
+        self.rect(x - width / 2.0, y - height / 2.0, width, height)
+    }
+}
@@ -1,22 +1,186 @@
-use crate::road::{ROAD_WIDTH};
-use crate::vehicle::Vehicle;
-use rand::Rng;
-use sdl2::rect::Rect;
+use crate::pedestrian::Pedestrian;
+use crate::road::{
+    Approach, Crosswalk, IntersectionGeometry, JunctionId, JunctionKind, LaneId, LaneKind, Movement, RoadNetwork,
+    ROUNDABOUT_RADIUS,
+};
+use crate::vehicle::{Vehicle, SAFETY_GAP};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sdl2::gfx::primitives::DrawRenderer;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 use sdl2::mixer::{InitFlag, AUDIO_S16LSB, DEFAULT_CHANNELS};
 // use std::f64::consts::PI;
-use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::Duration;
 
-use crate::vehicle::Lane;
+use crate::clock::SimClock;
+use crate::controller::{Command, FcfsController, IntersectionController};
+use crate::event::{EventKind, WorldEvent};
+use crate::gridlock::{end_of_waits, find_cycles, Gridlock, GridlockPolicy, Standstill, DEFAULT_GRIDLOCK_TICKS};
+use crate::safety::{CloseCall, Motion, SafetyMonitor, SeverityThresholds};
+use crate::scenario::Scenario;
+use crate::spatial::SpatialGrid;
+use crate::view::View;
+use smart_road::PanelStats;
+
+/// Callback invoked for every event as soon as it is emitted.
+pub type EventListener = Box<dyn FnMut(&WorldEvent)>;
+
+/// Time between spawns when vehicles enter from the four sides of a single intersection; scenarios
+/// with more entries spawn proportionally faster.
+const SPAWN_COOLDOWN_PER_FOUR_ENTRIES: Duration = Duration::from_millis(950);
+/// Vehicle limit per junction.
+const MAX_VEHICLES_PER_JUNCTION: usize = 18;
+/// Width of a painted lane marking in meters.
+const LANE_MARKING_WIDTH: f64 = 0.3;
+/// Factor from m/s to the km/h shown in the stats.
+const KMH_PER_MS: f64 = 3.6;
+/// Pedestrian limit per junction.
+const MAX_PEDESTRIANS_PER_JUNCTION: usize = 8;
+/// Width of a painted crosswalk stripe in meters.
+const CROSSWALK_STRIPE_WIDTH: f64 = 0.5;
+/// How far ahead along its route a vehicle looks for crosswalks with pedestrians, in meters.
+const CROSSWALK_LOOKAHEAD: f64 = 20.0;
+/// Spacing in meters of the points sampled along a vehicle's route when looking for crosswalks.
+const CROSSWALK_STEP: f64 = 0.5;
+/// Room in meters a vehicle leaves beside its path to a pedestrian on the crosswalk.
+const PEDESTRIAN_CLEARANCE: f64 = 1.0;
+/// Distance in meters between a vehicle's footprint and a crossing pedestrian counted as a conflict.
+const PEDESTRIAN_CONFLICT_DISTANCE: f64 = 0.5;
+/// Seconds a driver takes to react to a pedestrian stepping onto the crosswalk.
+const PEDESTRIAN_REACTION_TIME: f64 = 0.5;
+/// Side in meters of the grid cells used to find the vehicles near each other.
+const NEIGHBOUR_CELL_SIZE: f64 = 20.0;
+/// Color the footprints of two colliding vehicles are outlined in once the simulation halts.
+const COLLISION_HIGHLIGHT: sdl2::pixels::Color = sdl2::pixels::Color::RGB(255, 0, 0);
+
+/// Where a vehicle's route runs over a crosswalk.
+struct CrosswalkCrossing {
+    /// Index of the crosswalk in the `World`.
+    crosswalk: usize,
+    /// Distance the vehicle can drive before touching the crosswalk.
+    distance: f64,
+    /// Whether the vehicle is already on the crosswalk.
+    entered: bool,
+    /// Distance from the crosswalk's start of where the vehicle drives over it.
+    along: f64,
+}
+
+/// One intersection of the network, with its own controller and counters.
+struct Junction {
+    geometry: IntersectionGeometry,
+    /// Decides each tick which vehicles may enter this junction or must hold.
+    controller: Box<dyn IntersectionController>,
+    /// Vehicles that drove through the junction.
+    crossed: u32,
+    total_crossing_time: Duration,
+    max_crossing_time: Duration,
+    close_calls: u32,
+    collisions: u32,
+}
+
+impl Junction {
+    fn new(geometry: IntersectionGeometry) -> Self {
+        Junction {
+            geometry,
+            controller: Box::new(FcfsController),
+            crossed: 0,
+            total_crossing_time: Duration::ZERO,
+            max_crossing_time: Duration::ZERO,
+            close_calls: 0,
+            collisions: 0,
+        }
+    }
+
+    fn summary(&self, id: JunctionId) -> String {
+        let mean_crossing_time = if self.crossed == 0 {
+            Duration::ZERO
+        } else {
+            self.total_crossing_time / self.crossed
+        };
+        format!(
+            "Junction {}: crossed {}, mean crossing {:.2} s, max crossing {:.2} s, close calls {}, collisions {}",
+            id,
+            self.crossed,
+            mean_crossing_time.as_secs_f64(),
+            self.max_crossing_time.as_secs_f64(),
+            self.close_calls,
+            self.collisions,
+        )
+    }
+}
 
 pub struct World {
+    scenario: Scenario,
+    /// Junctions of the scenario, indexed by `JunctionId`; spawn points, routes, stop lines and the
+    /// drawn roads come from their geometry.
+    junctions: Vec<Junction>,
+    /// Lane graph built from the junctions, shared with every vehicle driving on it.
+    network: Rc<RoadNetwork>,
+    /// Junction legs vehicles enter the map from.
+    entries: Vec<(JunctionId, Approach)>,
+    /// Crosswalk across every leg of every junction.
+    crosswalks: Vec<(JunctionId, Crosswalk)>,
     vehicles: Vec<Vehicle>,
-    spawn_sound: sdl2::mixer::Chunk,
-    last_vehicle_spawn_time: Instant,
+    next_vehicle_id: u32,
+    pedestrians: Vec<Pedestrian>,
+    next_pedestrian_id: u32,
+    /// Time between pedestrian spawns; `None` when no pedestrians are spawned.
+    pedestrian_spawn_interval: Option<Duration>,
+    last_pedestrian_spawn_time: Duration,
+    pedestrians_crossed: u32,
+    /// Time pedestrians waited on the curb before crossing.
+    total_pedestrian_wait: Duration,
+    max_pedestrian_wait: Duration,
+    pedestrian_conflicts: u32,
+    /// Vehicle and pedestrian ids of the pairs currently in conflict, so each conflict counts once.
+    pedestrian_conflict_pairs: HashSet<(u32, u32)>,
+    /// Every close call of the session in the order they started, kept after the vehicles are gone.
+    close_calls: Vec<CloseCall>,
+    /// Index in `close_calls` of the close call each pair of vehicles is in now, lower id first.
+    ongoing_close_calls: HashMap<(u32, u32), usize>,
+    /// Times two vehicles' footprints started to overlap.
+    collisions: u32,
+    /// Ids of the vehicle pairs currently overlapping, lower id first, so each collision counts once.
+    colliding_pairs: HashSet<(u32, u32)>,
+    /// Whether the simulation freezes on the first collision.
+    halt_on_collision: bool,
+    /// The pair of vehicles whose collision froze the simulation.
+    halted_on: Option<(u32, u32)>,
+    /// Ticks every vehicle of a circular wait must have stood still for before it counts as a gridlock.
+    gridlock_ticks: u64,
+    /// Picks the vehicle of a gridlock that goes first.
+    gridlock_policy: GridlockPolicy,
+    /// Every gridlock of the session, in the order they were broken.
+    gridlocks: Vec<Gridlock>,
+    /// Last tick any vehicle moved on; `gridlock_ticks` later without a move is a standstill.
+    last_move_tick: u64,
+    /// Vehicles let past their controller's hold to end a standstill, until they are in the junction.
+    released_holds: HashSet<u32>,
+    /// Every standstill of the session, in the order they were broken.
+    standstills: Vec<Standstill>,
+    /// Time-to-collision, post-encroachment time and deceleration rate to avoid the crash of every
+    /// pair of interacting vehicles.
+    safety: SafetyMonitor,
+    /// Events of the most recent tick, kept until drained or until the next `update`.
+    events: Vec<WorldEvent>,
+    listeners: Vec<EventListener>,
+    /// `None` when running headless, in which case spawning is silent.
+    spawn_sound: Option<sdl2::mixer::Chunk>,
+    /// Every time-based field and the spawn cooldown read from this clock.
+    clock: SimClock,
+    /// Single random source for spawning and vehicle updates, so a run can be replayed from its seed.
+    rng: StdRng,
+    seed: u64,
+    last_vehicle_spawn_time: Duration,
     vehicle_spawn_cooldown: Duration,
     max_vehicles: usize,
     vehicle_passed: u32,
+    passed_by_approach: HashMap<Approach, u32>,
+    passed_by_movement: HashMap<Movement, u32>,
+    /// Highest and lowest average speed of a finished vehicle, in m/s.
     max_velocity: f64,
     min_velocity: f64,
     max_crossing_time: Duration,
@@ -25,20 +189,79 @@ pub struct World {
 
 #[allow(dead_code)]
 impl World {
-    pub fn new(_sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(_sdl_context: &sdl2::Sdl, seed: u64, scenario: Scenario) -> Self {
         sdl2::mixer::open_audio(44100, AUDIO_S16LSB, DEFAULT_CHANNELS, 1024).unwrap();
         let _mixer_context = sdl2::mixer::init(InitFlag::MP3).unwrap();
         sdl2::mixer::allocate_channels(8);
         let mut spawn_sound = sdl2::mixer::Chunk::from_file("./src/assets/car-spawn.mp3").unwrap();
         spawn_sound.set_volume(16); // Set volume between 0-128, where 128 is max volume
 
+        Self::with_spawn_sound(Some(spawn_sound), seed, scenario)
+    }
+
+    /// Creates a world that never touches SDL, for running without a display or sound card.
+    pub fn headless(seed: u64, scenario: Scenario) -> Self {
+        Self::with_spawn_sound(None, seed, scenario)
+    }
+
+    fn with_spawn_sound(spawn_sound: Option<sdl2::mixer::Chunk>, seed: u64, scenario: Scenario) -> Self {
+        let geometries = scenario.geometries();
+        let network = RoadNetwork::new(&geometries, |junction, side| scenario.neighbour(junction, side));
+        let entries: Vec<(JunctionId, Approach)> = (0..geometries.len())
+            .flat_map(|junction| Approach::ALL.into_iter().map(move |approach| (junction, approach)))
+            .filter(|&(junction, approach)| {
+                geometries[junction].has_leg(approach) && scenario.neighbour(junction, approach).is_none()
+            })
+            .collect();
+        let crosswalks = geometries.iter().enumerate()
+            .flat_map(|(junction, geometry)| {
+                Approach::ALL.into_iter()
+                    .filter(|&side| geometry.has_leg(side))
+                    .map(move |side| (junction, geometry.crosswalk(side)))
+            })
+            .collect();
         World {
+            max_vehicles: MAX_VEHICLES_PER_JUNCTION * scenario.junction_count(),
+            scenario,
+            junctions: geometries.into_iter().map(Junction::new).collect(),
+            network: Rc::new(network),
+            vehicle_spawn_cooldown: SPAWN_COOLDOWN_PER_FOUR_ENTRIES * 4 / entries.len() as u32,
+            entries,
+            crosswalks,
             vehicles: Vec::new(),
+            next_vehicle_id: 1,
+            pedestrians: Vec::new(),
+            next_pedestrian_id: 1,
+            pedestrian_spawn_interval: None,
+            last_pedestrian_spawn_time: Duration::ZERO,
+            pedestrians_crossed: 0,
+            total_pedestrian_wait: Duration::ZERO,
+            max_pedestrian_wait: Duration::ZERO,
+            pedestrian_conflicts: 0,
+            pedestrian_conflict_pairs: HashSet::new(),
+            close_calls: Vec::new(),
+            ongoing_close_calls: HashMap::new(),
+            collisions: 0,
+            colliding_pairs: HashSet::new(),
+            halt_on_collision: false,
+            halted_on: None,
+            gridlock_ticks: DEFAULT_GRIDLOCK_TICKS,
+            gridlock_policy: GridlockPolicy::default(),
+            gridlocks: Vec::new(),
+            last_move_tick: 0,
+            released_holds: HashSet::new(),
+            standstills: Vec::new(),
+            safety: SafetyMonitor::new(SeverityThresholds::default()),
+            events: Vec::new(),
+            listeners: Vec::new(),
             spawn_sound,
-            last_vehicle_spawn_time: Instant::now(),
-            vehicle_spawn_cooldown: Duration::from_millis(950),
-            max_vehicles: 18,
+            clock: SimClock::default(),
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+            last_vehicle_spawn_time: Duration::ZERO,
             vehicle_passed: 0,
+            passed_by_approach: HashMap::new(),
+            passed_by_movement: HashMap::new(),
             max_velocity: 0.0,
             min_velocity: 0.0,
             max_crossing_time: Duration::from_secs(0),
@@ -46,28 +269,110 @@ impl World {
         }
     }
 
+    /// Advances the simulation by one tick, unless it halted on a collision.
     pub fn update(&mut self) {
-        for i in 0..self.vehicles.len() {
-            let (current, others) = self.vehicles.split_at_mut(i);
-            if let Some((vehicle, rest)) = others.split_first_mut() {
-                let mut collision_check = current.to_vec();
-                collision_check.extend_from_slice(rest);
-                vehicle.update(&collision_check);
+        if self.halted_on.is_some() {
+            return;
+        }
+        let now = self.clock.now();
+        let tick = self.clock.tick();
+        self.events.retain(|e| e.tick >= tick);
+
+        // Each junction's controller only sees the vehicles driving towards or through it.
+        let mut commands = vec![Command::Proceed; self.vehicles.len()];
+        let mut by_junction = vec![Vec::new(); self.junctions.len()];
+        for (i, vehicle) in self.vehicles.iter().enumerate() {
+            if let Some(junction) = vehicle.junction() {
+                by_junction[junction].push(i);
+            }
+        }
+        for (junction, indices) in self.junctions.iter_mut().zip(by_junction) {
+            let vehicles: Vec<&Vehicle> = indices.iter().map(|&i| &self.vehicles[i]).collect();
+            for (i, command) in indices.into_iter().zip(junction.controller.decide(&vehicles, &self.clock)) {
+                commands[i] = command;
+            }
+        }
+        for (command, vehicle) in commands.iter_mut().zip(&self.vehicles) {
+            if self.released_holds.contains(&vehicle.id) {
+                *command = Command::Proceed;
+            }
+        }
+
+        // Whatever the controllers say, vehicles yield to pedestrians on the crosswalks ahead.
+        let on_crosswalk = self.pedestrians_by_crosswalk();
+        let crossings: Vec<Vec<CrosswalkCrossing>> =
+            self.vehicles.iter().map(|v| self.crossings_ahead(v, &on_crosswalk)).collect();
+        for ((command, vehicle), crossings) in commands.iter_mut().zip(&self.vehicles).zip(&crossings) {
+            if let Some(distance) = self.pedestrian_stop(vehicle, crossings, &on_crosswalk) {
+                *command = command.and(Command::StopWithin(distance));
             }
         }
 
+        // Vehicles move in turn, each looking only at the others near it, found through a grid of
+        // their positions kept up to date as they move.
+        let mut grid = SpatialGrid::new(NEIGHBOUR_CELL_SIZE, self.vehicles.iter().map(|v| (v.x, v.y)));
+        let mut events = Vec::new();
+        for (i, &command) in commands.iter().enumerate() {
+            let (before, rest) = self.vehicles.split_at_mut(i);
+            if let Some((vehicle, after)) = rest.split_first_mut() {
+                let neighbours: Vec<&Vehicle> = grid.query((vehicle.x, vehicle.y), vehicle.interaction_radius())
+                    .into_iter()
+                    .filter(|&j| j != i)
+                    .map(|j| if j < i { &before[j] } else { &after[j - i - 1] })
+                    .collect();
+
+                let position = (vehicle.x, vehicle.y);
+                let junction = vehicle.junction();
+                let was_approaching = vehicle.is_approaching_intersection();
+                let entry_time = vehicle.intersection_entry_time;
+                vehicle.update(&neighbours, command, &self.clock);
+                grid.relocate(i, position, (vehicle.x, vehicle.y));
+                if (vehicle.x, vehicle.y) != position {
+                    self.last_move_tick = tick;
+                }
+
+                if let (false, true, Some(junction)) =
+                    (was_approaching, vehicle.is_approaching_intersection(), vehicle.junction())
+                {
+                    events.push((vehicle.id, EventKind::Approaching { junction }));
+                }
+                match (entry_time, vehicle.intersection_entry_time, junction) {
+                    (None, Some(_), Some(junction)) => {
+                        events.push((vehicle.id, EventKind::EnteredIntersection { junction }));
+                    }
+                    (Some(entry_time), None, Some(junction)) => {
+                        events.push((vehicle.id, EventKind::ExitedIntersection { junction }));
+                        let stats = &mut self.junctions[junction];
+                        let crossing_time = now.saturating_sub(entry_time);
+                        stats.crossed += 1;
+                        stats.total_crossing_time += crossing_time;
+                        stats.max_crossing_time = stats.max_crossing_time.max(crossing_time);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.observe_safety();
+
         for vehicle in &self.vehicles {
             if vehicle.is_finished() {
-                let crossing_time = vehicle.spawn_time.elapsed();
+                for (other, measures, severity) in self.safety.finish(vehicle.id) {
+                    events.push((vehicle.id, EventKind::Conflict { other, severity, measures }));
+                }
+                events.push((vehicle.id, EventKind::Finished));
+                *self.passed_by_approach.entry(vehicle.approach).or_insert(0) += 1;
+                *self.passed_by_movement.entry(vehicle.movement).or_insert(0) += 1;
+                let crossing_time = now.saturating_sub(vehicle.spawn_time);
                 self.max_crossing_time = self.max_crossing_time.max(crossing_time);
                 self.min_crossing_time = if self.min_crossing_time.as_nanos() == 0 {
                     crossing_time
                 } else {
                     self.min_crossing_time.min(crossing_time)
                 };
-                let vel = vehicle.get_velocity(&self.vehicles);
+                let vel = vehicle.average_velocity(self.clock.dt());
                 self.max_velocity = self.max_velocity.max(vel);
-                if self.min_velocity == 0.0 || self.min_velocity == f64::INFINITY {
+                if self.min_velocity == 0.0 {
                     self.min_velocity = vel;
                 } else {
                     self.min_velocity = self.min_velocity.min(vel);
@@ -75,34 +380,573 @@ impl World {
             }
         }
 
-        if !self.vehicles.is_empty() {
-            let current_max = self.vehicles.iter()
-                .map(|v| v.get_velocity(&self.vehicles))
-                .fold(0.0, f64::max);
-            self.max_velocity = self.max_velocity.max(current_max);
-    
-            let current_min = self.vehicles.iter()
-                .map(|v| v.get_velocity(&self.vehicles))
-                .fold(f64::INFINITY, f64::min);
-            if current_min != f64::INFINITY {
-                self.min_velocity = self.min_velocity.min(current_min);
-            }
-        }
-
         let finished_count = self.vehicles.iter().filter(|v| v.is_finished()).count();
         self.vehicle_passed += finished_count as u32;
         // self.max_velocity = self.vehicles.iter().map(|v| v.get_velocity(&self.vehicles)).fold(0.0, f64::max);
         // self.min_velocity = self.vehicles.iter().map(|v| v.get_velocity(&self.vehicles)).fold(f64::INFINITY, f64::min);
         //println!("Vehicles passed: {}", self.vehicle_passed);
         self.vehicles.retain(|v| !v.is_finished());
+        self.detect_close_calls(&mut events);
+        self.detect_collisions(&mut events);
+        self.break_gridlocks(&mut events);
+        self.break_standstill(&mut events);
+        self.update_pedestrians(&crossings, &mut events);
+        for (vehicle_id, kind) in events {
+            self.emit(vehicle_id, kind);
+        }
+        self.clock.advance();
     }
 
-    pub fn get_total_close_call_count(&self) -> u32 {
-        let mut total = 0;
+    /// Logs the pairs of vehicles from different directions that came within the safety gap of each
+    /// other, emitting a `CloseCall` event for the older vehicle of each as the close call starts, and
+    /// keeps the closest approach of the close calls still going on.
+    fn detect_close_calls(&mut self, events: &mut Vec<(u32, EventKind)>) {
+        let tick = self.clock.tick();
+        let grid = SpatialGrid::new(NEIGHBOUR_CELL_SIZE, self.vehicles.iter().map(|v| (v.x, v.y)));
+        let reach = self.vehicles.iter().map(|v| v.footprint().radius()).fold(0.0, f64::max);
+        let mut ongoing = HashMap::new();
+        for (i, vehicle) in self.vehicles.iter().enumerate() {
+            let footprint = vehicle.footprint();
+            let radius = footprint.radius() + reach + SAFETY_GAP;
+            for j in grid.query((vehicle.x, vehicle.y), radius).into_iter().filter(|&j| j > i) {
+                let other = &self.vehicles[j];
+                if vehicle.junction_approach() == other.junction_approach() {
+                    continue;
+                }
+                let distance = footprint.distance(&other.footprint());
+                if distance >= SAFETY_GAP {
+                    continue;
+                }
+                let (first, second) = if vehicle.id < other.id { (vehicle, other) } else { (other, vehicle) };
+                let pair = (first.id, second.id);
+                let position = ((first.x + second.x) / 2.0, (first.y + second.y) / 2.0);
+                if let Some(&index) = self.ongoing_close_calls.get(&pair) {
+                    let close_call = &mut self.close_calls[index];
+                    if distance < close_call.min_distance {
+                        close_call.closest_tick = tick;
+                        close_call.position = position;
+                        close_call.min_distance = distance;
+                    }
+                    ongoing.insert(pair, index);
+                    continue;
+                }
+                ongoing.insert(pair, self.close_calls.len());
+                self.close_calls.push(CloseCall {
+                    vehicles: pair,
+                    started: tick,
+                    closest_tick: tick,
+                    position,
+                    min_distance: distance,
+                });
+                if let Some(junction) = first.junction().or(second.junction()) {
+                    self.junctions[junction].close_calls += 1;
+                }
+                events.push((first.id, EventKind::CloseCall {
+                    position: (first.x, first.y),
+                    other: second.id,
+                    distance,
+                }));
+            }
+        }
+        self.ongoing_close_calls = ongoing;
+    }
+
+    /// Counts the vehicle pairs whose footprints started to overlap this tick, emitting a `Collision`
+    /// event for the older vehicle of each, and halts on the first one if asked to.
+    fn detect_collisions(&mut self, events: &mut Vec<(u32, EventKind)>) {
+        let grid = SpatialGrid::new(NEIGHBOUR_CELL_SIZE, self.vehicles.iter().map(|v| (v.x, v.y)));
+        let reach = self.vehicles.iter().map(|v| v.footprint().radius()).fold(0.0, f64::max);
+        let mut colliding_pairs = HashSet::new();
+        for (i, vehicle) in self.vehicles.iter().enumerate() {
+            let footprint = vehicle.footprint();
+            for j in grid.query((vehicle.x, vehicle.y), footprint.radius() + reach).into_iter().filter(|&j| j > i) {
+                let other = &self.vehicles[j];
+                if footprint.overlaps(&other.footprint()) {
+                    let (first, second) = if vehicle.id < other.id { (vehicle, other) } else { (other, vehicle) };
+                    let pair = (first.id, second.id);
+                    if colliding_pairs.insert(pair) && !self.colliding_pairs.contains(&pair) {
+                        self.collisions += 1;
+                        if let Some(junction) = first.junction().or(second.junction()) {
+                            self.junctions[junction].collisions += 1;
+                        }
+                        events.push((first.id, EventKind::Collision {
+                            position: (first.x, first.y),
+                            other: second.id,
+                            other_position: (second.x, second.y),
+                        }));
+                        if self.halt_on_collision && self.halted_on.is_none() {
+                            self.halted_on = Some(pair);
+                        }
+                    }
+                }
+            }
+        }
+        self.colliding_pairs = colliding_pairs;
+    }
+
+    /// Finds the circular waits whose vehicles have all stood still for `gridlock_ticks`, emitting a
+    /// `Gridlock` event for each vehicle in them. The vehicle the policy picks is given priority over
+    /// the one it waits for. Until that priority runs out, whatever holds the vehicle back makes way
+    /// for it, and so does whatever keeps those from backing off.
+    fn break_gridlocks(&mut self, events: &mut Vec<(u32, EventKind)>) {
+        let ids: HashMap<u32, usize> = self.vehicles.iter().enumerate().map(|(i, v)| (v.id, i)).collect();
+        let clearing = |v: &Vehicle| v.priority_over.is_some() || v.yielding_to.is_some();
+        let released: Vec<usize> = self.vehicles.iter()
+            .enumerate()
+            .filter(|&(_, v)| {
+                v.yielding_to.is_some_and(|id| ids.get(&id).is_none_or(|&i| !clearing(&self.vehicles[i])))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let blocking: Vec<(usize, u32)> = self.vehicles.iter()
+            .filter(|v| clearing(v) && v.waiting_for != v.yielding_to)
+            .filter_map(|v| {
+                let i = ids.get(&v.waiting_for?).copied()?;
+                (!clearing(&self.vehicles[i])).then_some((i, v.id))
+            })
+            .collect();
+        for i in released {
+            self.vehicles[i].yielding_to = None;
+        }
+        for (i, id) in blocking {
+            self.vehicles[i].yielding_to = Some(id);
+        }
+
+        // The vehicles of a gridlock broken less than `gridlock_ticks` ago are left out while that has
+        // time to work. If it does not, the vehicle given priority is passed over the next time.
+        let tick = self.clock.tick();
+        let recent: HashSet<u32> = self.gridlocks.iter()
+            .rev()
+            .take_while(|gridlock| tick < gridlock.tick + self.gridlock_ticks)
+            .flat_map(|gridlock| gridlock.cycle.iter().copied())
+            .collect();
+        let waits: HashMap<u32, u32> = self.vehicles.iter()
+            .filter(|v| v.stopped_ticks >= self.gridlock_ticks && !recent.contains(&v.id))
+            .filter_map(|v| v.waiting_for.map(|other| (v.id, other)))
+            .collect();
+        for cycle in find_cycles(&waits) {
+            let candidates: Vec<u32> = cycle.iter()
+                .copied()
+                .filter(|&id| ids.get(&id).is_some_and(|&i| self.vehicles[i].priority_over.is_none()))
+                .collect();
+            let candidates = if candidates.is_empty() { &cycle } else { &candidates };
+            let stopped_ticks = |id: u32| ids.get(&id).map_or(0, |&i| self.vehicles[i].stopped_ticks);
+            let released = self.gridlock_policy.pick(candidates, stopped_ticks);
+            for (i, &id) in cycle.iter().enumerate() {
+                events.push((id, EventKind::Gridlock { waiting_for: cycle[(i + 1) % cycle.len()] }));
+            }
+            let over = waits[&released];
+            for vehicle in &mut self.vehicles {
+                if vehicle.id == released {
+                    (vehicle.priority_over, vehicle.yielding_to) = (Some(over), None);
+                } else if vehicle.id == over {
+                    (vehicle.priority_over, vehicle.yielding_to) = (None, Some(released));
+                }
+            }
+            events.push((released, EventKind::PriorityOverride { over }));
+            self.gridlocks.push(Gridlock { tick, cycle, released });
+        }
+    }
+
+    /// Finds a standstill: no vehicle has moved for `gridlock_ticks` while some stand still in a
+    /// junction, which no red light or pedestrian keeps them at for long. The waits of those vehicles
+    /// lead to vehicles held by their controller, e.g. arrivals kept out of a full junction; the one
+    /// the policy picks is let past its hold until it is in the junction.
+    fn break_standstill(&mut self, events: &mut Vec<(u32, EventKind)>) {
+        let vehicles = &self.vehicles;
+        self.released_holds.retain(|&id| vehicles.iter().any(|v| v.id == id && !v.is_in_intersection()));
+        let tick = self.clock.tick();
+        if tick < self.last_move_tick + self.gridlock_ticks {
+            return;
+        }
+        let waits: HashMap<u32, u32> = vehicles.iter()
+            .filter_map(|v| v.waiting_for.map(|other| (v.id, other)))
+            .collect();
+        let mut held: HashMap<u32, Vec<u32>> = HashMap::new();
+        for vehicle in vehicles.iter().filter(|v| v.occupies_intersection() && v.stopped_ticks >= self.gridlock_ticks) {
+            if let Some(end) = end_of_waits(&waits, vehicle.id).filter(|end| !self.released_holds.contains(end)) {
+                held.entry(end).or_default().push(vehicle.id);
+            }
+        }
+        let mut holding: Vec<u32> = held.keys().copied().collect();
+        if holding.is_empty() {
+            return;
+        }
+        holding.sort_unstable();
+        let stopped_ticks = |id: u32| vehicles.iter().find(|v| v.id == id).map_or(0, |v| v.stopped_ticks);
+        let released = self.gridlock_policy.pick(&holding, stopped_ticks);
+        let mut held = held.remove(&released).unwrap();
+        held.sort_unstable();
+        events.push((released, EventKind::HoldReleased { holding: held[0] }));
+        self.released_holds.insert(released);
+        // Give the released vehicle as long to get going before looking for another.
+        self.last_move_tick = tick;
+        self.standstills.push(Standstill { tick, held, released });
+    }
+
+    /// Counts vehicles as gridlocked once every vehicle of a circular wait has stood still for `ticks`,
+    /// and as in a standstill once no vehicle has moved for `ticks`, and breaks both by `policy`.
+    pub fn set_gridlock_resolution(&mut self, ticks: u64, policy: GridlockPolicy) {
+        self.gridlock_ticks = ticks;
+        self.gridlock_policy = policy;
+    }
+
+    /// Every gridlock of the session, in the order they were broken.
+    pub fn gridlocks(&self) -> &[Gridlock] {
+        &self.gridlocks
+    }
+
+    /// Summary of the gridlocks broken, with their cycles, and of the standstills broken, if any.
+    fn gridlock_summary(&self) -> String {
+        let cycles = self.gridlocks.iter().map(|gridlock| gridlock.to_string()).collect::<Vec<_>>().join("; ");
+        let mut summary = if cycles.is_empty() {
+            format!("Gridlocks: 0, broken by {}", self.gridlock_policy)
+        } else {
+            format!("Gridlocks: {}, broken by {}: {}", self.gridlocks.len(), self.gridlock_policy, cycles)
+        };
+        if !self.standstills.is_empty() {
+            let standstills = self.standstills.iter().map(|standstill| standstill.to_string()).collect::<Vec<_>>();
+            summary += &format!("; standstills: {}: {}", standstills.len(), standstills.join("; "));
+        }
+        summary
+    }
+
+    /// Feeds the vehicles' positions and velocities after this tick's moves to the safety monitor: each
+    /// pair that could meet within the time-to-collision horizon, and the ground each vehicle in a
+    /// junction covers.
+    fn observe_safety(&mut self) {
+        let tick = self.clock.tick();
+        let dt = self.clock.dt();
+        let motions: Vec<Motion> = self.vehicles.iter()
+            .map(|v| Motion { id: v.id, footprint: v.footprint(), velocity: v.velocity() })
+            .collect();
+        let reach = motions.iter().map(|m| m.footprint.radius()).fold(0.0, f64::max);
+        let fastest = self.vehicles.iter().map(|v| v.speed).fold(0.0, f64::max);
+        let horizon = self.safety.horizon();
+        // Cells as large as the longest query, so each query looks at no more than nine of them.
+        let cell_size = (2.0 * (reach + fastest * horizon)).max(NEIGHBOUR_CELL_SIZE);
+        let grid = SpatialGrid::new(cell_size, self.vehicles.iter().map(|v| (v.x, v.y)));
+        for (i, vehicle) in self.vehicles.iter().enumerate() {
+            let radius = motions[i].footprint.radius() + reach + (vehicle.speed + fastest) * horizon;
+            for j in grid.query((vehicle.x, vehicle.y), radius).into_iter().filter(|&j| j > i) {
+                self.safety.observe_pair(&motions[i], &motions[j]);
+            }
+            if vehicle.occupies_intersection() {
+                self.safety.observe_occupancy(&motions[i], vehicle.junction_approach(), tick, dt);
+            }
+        }
+    }
+
+    /// Groups conflicts by the given severity thresholds instead of the defaults. Call before the first
+    /// `update`, as it forgets the conflicts measured so far.
+    pub fn set_severity_thresholds(&mut self, thresholds: SeverityThresholds) {
+        self.safety = SafetyMonitor::new(thresholds);
+    }
+
+    /// Makes the simulation freeze on the first collision, keeping the colliding pair on screen.
+    pub fn set_halt_on_collision(&mut self, halt: bool) {
+        self.halt_on_collision = halt;
+    }
+
+    /// Ids of the two vehicles whose collision halted the simulation, if it halted.
+    pub fn halted_on(&self) -> Option<(u32, u32)> {
+        self.halted_on
+    }
+
+    /// Crosswalks with pedestrians that the vehicle's route runs over in the next
+    /// `CROSSWALK_LOOKAHEAD` meters, in driving order. `on_crosswalk` comes from
+    /// `pedestrians_by_crosswalk`.
+    fn crossings_ahead(&self, vehicle: &Vehicle, on_crosswalk: &[Vec<usize>]) -> Vec<CrosswalkCrossing> {
+        let reach = CROSSWALK_LOOKAHEAD + vehicle.length();
+        let nearby: Vec<usize> = (0..self.crosswalks.len())
+            .filter(|&index| !on_crosswalk[index].is_empty())
+            .filter(|&index| {
+                let area = self.crosswalks[index].1.area;
+                vehicle.x > area.left - reach && vehicle.x < area.right + reach
+                    && vehicle.y > area.top - reach && vehicle.y < area.bottom + reach
+            })
+            .collect();
+        if nearby.is_empty() {
+            return Vec::new();
+        }
+
+        let mut crossings: Vec<CrosswalkCrossing> = Vec::new();
+        let points = std::iter::once((vehicle.x, vehicle.y))
+            .chain(vehicle.predicted_path(CROSSWALK_STEP, CROSSWALK_LOOKAHEAD));
+        for (step, (x, y)) in points.enumerate() {
+            for &index in &nearby {
+                let crosswalk = &self.crosswalks[index].1;
+                if crosswalk.area.overlaps(x, y, vehicle.length() / 2.0) && crossings.iter().all(|c| c.crosswalk != index) {
+                    crossings.push(CrosswalkCrossing {
+                        crosswalk: index,
+                        // Up to the last sampled point clear of the crosswalk.
+                        distance: step.saturating_sub(1) as f64 * CROSSWALK_STEP,
+                        entered: step == 0,
+                        along: crosswalk.distance_along(x, y),
+                    });
+                }
+            }
+        }
+        crossings
+    }
+
+    /// Distance a vehicle may drive before stopping for a pedestrian, if it has to: it yields at the
+    /// first crosswalk ahead with a pedestrian near its path or walking towards it. A vehicle already
+    /// on a crosswalk clears it.
+    fn pedestrian_stop(
+        &self,
+        vehicle: &Vehicle,
+        crossings: &[CrosswalkCrossing],
+        on_crosswalk: &[Vec<usize>],
+    ) -> Option<f64> {
+        let clearance = vehicle.width() / 2.0 + PEDESTRIAN_CLEARANCE;
+        crossings.iter()
+            .filter(|crossing| !crossing.entered)
+            .find(|crossing| {
+                on_crosswalk[crossing.crosswalk].iter().map(|&i| &self.pedestrians[i]).any(|pedestrian| {
+                    pedestrian.is_crossing()
+                        && ((pedestrian.position() - crossing.along).abs() < clearance
+                            || pedestrian.is_walking_towards(crossing.along))
+                })
+            })
+            .map(|crossing| crossing.distance)
+    }
+
+    /// Lets waiting pedestrians step onto their crosswalk, walks the others, counts conflicts with
+    /// vehicles and removes the pedestrians that reached the other curb.
+    ///
+    /// A pedestrian starts crossing when the junction's controller allows it, no vehicle is on the
+    /// crosswalk, and every vehicle about to drive over it can still stop comfortably before it.
+    /// `crossings` are those of every vehicle at the start of the tick.
+    fn update_pedestrians(&mut self, crossings: &[Vec<CrosswalkCrossing>], events: &mut Vec<(u32, EventKind)>) {
+        let now = self.clock.now();
+        let dt = self.clock.dt();
+        // Crosswalks some vehicle is on, or about to drive over without room to stop for a pedestrian.
+        let mut blocked = vec![false; self.crosswalks.len()];
+        for (vehicle, crossings) in self.vehicles.iter().zip(crossings) {
+            for crossing in crossings {
+                blocked[crossing.crosswalk] |= crossing.entered
+                    || vehicle.braking_distance() + vehicle.speed * PEDESTRIAN_REACTION_TIME >= crossing.distance;
+            }
+        }
+        for i in 0..self.pedestrians.len() {
+            let pedestrian = &self.pedestrians[i];
+            if !pedestrian.is_crossing() {
+                let controller = &self.junctions[pedestrian.junction].controller;
+                if blocked[pedestrian.crosswalk_index] || !controller.may_cross(pedestrian.crosswalk.side) {
+                    continue;
+                }
+                let wait = now.saturating_sub(pedestrian.spawn_time);
+                self.total_pedestrian_wait += wait;
+                self.max_pedestrian_wait = self.max_pedestrian_wait.max(wait);
+                self.pedestrians[i].start_crossing(now);
+            }
+            self.pedestrians[i].update(dt);
+        }
+
+        let conflict_pairs = self.pedestrians_near_vehicles(PEDESTRIAN_CONFLICT_DISTANCE);
+        for &(vehicle_id, pedestrian_id) in conflict_pairs.difference(&self.pedestrian_conflict_pairs) {
+            self.pedestrian_conflicts += 1;
+            events.push((vehicle_id, EventKind::PedestrianConflict { pedestrian: pedestrian_id }));
+        }
+        self.pedestrian_conflict_pairs = conflict_pairs;
+
+        self.pedestrians_crossed += self.pedestrians.iter().filter(|p| p.is_finished()).count() as u32;
+        self.pedestrians.retain(|p| !p.is_finished());
+    }
+
+    /// Indices of the pedestrians waiting at or walking over each crosswalk, indexed like `crosswalks`.
+    fn pedestrians_by_crosswalk(&self) -> Vec<Vec<usize>> {
+        let mut on_crosswalk = vec![Vec::new(); self.crosswalks.len()];
+        for (i, pedestrian) in self.pedestrians.iter().enumerate() {
+            on_crosswalk[pedestrian.crosswalk_index].push(i);
+        }
+        on_crosswalk
+    }
+
+    /// Ids of each vehicle and crossing pedestrian within `distance` of the vehicle's footprint, found
+    /// through a grid of the pedestrians' positions.
+    fn pedestrians_near_vehicles(&self, distance: f64) -> HashSet<(u32, u32)> {
+        let grid = SpatialGrid::new(NEIGHBOUR_CELL_SIZE, self.pedestrians.iter().map(|p| (p.x, p.y)));
+        let mut pairs = HashSet::new();
         for vehicle in &self.vehicles {
-            total += vehicle.close_call_count;
+            for i in grid.query((vehicle.x, vehicle.y), vehicle.footprint().radius() + distance) {
+                let pedestrian = &self.pedestrians[i];
+                if pedestrian.is_crossing() && vehicle.is_near(pedestrian.x, pedestrian.y, distance) {
+                    pairs.insert((vehicle.id, pedestrian.id));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Spawns pedestrians `per_minute` times per simulated minute, on random crosswalks, once
+    /// `auto_spawn_pedestrians` is called every tick. Zero turns pedestrians off.
+    pub fn set_pedestrian_demand(&mut self, per_minute: f64) {
+        self.pedestrian_spawn_interval = (per_minute > 0.0).then(|| Duration::from_secs_f64(60.0 / per_minute));
+    }
+
+    /// Spawns a pedestrian on the curb of a random crosswalk when the pedestrian demand is due.
+    pub fn auto_spawn_pedestrians(&mut self) {
+        let Some(interval) = self.pedestrian_spawn_interval else {
+            return;
+        };
+        if self.pedestrians.len() >= MAX_PEDESTRIANS_PER_JUNCTION * self.junctions.len()
+            || self.clock.now().saturating_sub(self.last_pedestrian_spawn_time) < interval
+        {
+            return;
+        }
+
+        let index = self.rng.gen_range(0..self.crosswalks.len());
+        let (junction, crosswalk) = self.crosswalks[index];
+        let reversed = self.rng.gen_bool(0.5);
+        let id = self.next_pedestrian_id;
+        self.next_pedestrian_id += 1;
+        self.pedestrians.push(Pedestrian::new(id, junction, index, crosswalk, reversed, self.clock.now()));
+        self.last_pedestrian_spawn_time = self.clock.now();
+    }
+
+    /// Summary of the pedestrians that crossed: how long they waited and how often vehicles came too close.
+    fn pedestrian_summary(&self) -> String {
+        let started = self.pedestrians_crossed + self.pedestrians.iter().filter(|p| p.is_crossing()).count() as u32;
+        let mean_wait = if started == 0 { Duration::ZERO } else { self.total_pedestrian_wait / started };
+        format!(
+            "Pedestrians: crossed {}, mean wait {:.2} s, max wait {:.2} s, conflicts with vehicles {}",
+            self.pedestrians_crossed,
+            mean_wait.as_secs_f64(),
+            self.max_pedestrian_wait.as_secs_f64(),
+            self.pedestrian_conflicts,
+        )
+    }
+
+    /// Gives every junction its own controller, built from the junction's geometry.
+    pub fn set_controllers(
+        &mut self,
+        mut build: impl FnMut(&IntersectionGeometry) -> Result<Box<dyn IntersectionController>, String>,
+    ) -> Result<(), String> {
+        for junction in &mut self.junctions {
+            junction.controller = build(&junction.geometry)?;
+        }
+        Ok(())
+    }
+
+    /// Name of the intersection controller of the first junction.
+    pub fn controller_name(&self) -> &'static str {
+        self.junctions[0].controller.name()
+    }
+
+    /// Registers a callback that is invoked for every event as it happens.
+    pub fn subscribe(&mut self, listener: impl FnMut(&WorldEvent) + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Takes the events emitted since the last drain. Call once per tick, after `update`.
+    pub fn drain_events(&mut self) -> Vec<WorldEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn emit(&mut self, vehicle_id: u32, kind: EventKind) {
+        let event = WorldEvent { tick: self.clock.tick(), vehicle_id, kind };
+        for listener in &mut self.listeners {
+            listener(&event);
         }
-        total
+        self.events.push(event);
+    }
+
+    /// Adds a vehicle with the next free id, a class drawn from the scenario's vehicle mix and a random
+    /// top speed for that class, and emits its `Spawned` event.
+    ///
+    /// The vehicle starts on the inbound lane `start`, which must allow `movement`. Returns false, and
+    /// spawns nothing, when the vehicle ahead on the lane is too close for the drawn class to fit, or
+    /// when no route to the edge of the map is found.
+    fn push_vehicle(&mut self, start: LaneId, approach: Approach, movement: Movement) -> bool {
+        let class = self.scenario.vehicle_mix.pick(&mut self.rng);
+        let blocked = self.vehicles.iter()
+            .any(|other| other.lane == start && other.offset - other.length() / 2.0 < class.length() / 2.0);
+        if blocked {
+            return false;
+        }
+
+        let Ok(route) = self.plan_route(start, movement) else {
+            return false;
+        };
+        let id = self.next_vehicle_id;
+        self.next_vehicle_id += 1;
+        let (min_speed, max_speed) = class.top_speed_range();
+        let max_speed = self.rng.gen_range(min_speed..=max_speed);
+        let network = Rc::clone(&self.network);
+        self.vehicles.push(Vehicle::new(id, class, approach, movement, network, route, self.clock.now(), max_speed));
+        self.emit(id, EventKind::Spawned { class, approach, movement });
+        true
+    }
+
+    /// Route from the inbound lane `start` through `movement` to the edge of the map.
+    ///
+    /// At each following junction the next lane is picked at random. Once the route has crossed as many
+    /// junctions as the scenario has, it leaves the map where it can and otherwise goes straight, onto
+    /// lanes that go straight again, so it reaches the edge before crossing twice as many.
+    fn plan_route(&mut self, start: LaneId, movement: Movement) -> Result<Vec<LaneId>, String> {
+        let network = Rc::clone(&self.network);
+        let junction_count = self.junctions.len();
+        let rng = &mut self.rng;
+        let mut choices = 0;
+        network.route(start, 2 * junction_count + 1, |next| {
+            let is_movement = |lane: LaneId, movement: Movement| {
+                matches!(network.lane(lane).kind, LaneKind::Connector { movement: m, .. } if m == movement)
+            };
+            if next == network.lane(start).next.as_slice() {
+                // Leaving the first lane: keep to the movement the vehicle spawned for.
+                let lanes: Vec<LaneId> = next.iter().copied().filter(|&lane| is_movement(lane, movement)).collect();
+                return lanes[rng.gen_range(0..lanes.len())];
+            }
+            choices += 1;
+            if choices < junction_count {
+                return next[rng.gen_range(0..next.len())];
+            }
+            // Each connector ends in a single lane: an outbound lane, or an inbound lane of the next junction.
+            let exit = |connector: LaneId| network.lane(network.lane(connector).next[0]);
+            let leaves = |&&connector: &&LaneId| matches!(exit(connector).kind, LaneKind::Outbound { .. });
+            let straight = |&&connector: &&LaneId| is_movement(connector, Movement::Straight);
+            let reaches_straight = |&&connector: &&LaneId| {
+                exit(connector).next.iter().any(|&after| is_movement(after, Movement::Straight))
+            };
+            let goes_straight = |connector: &&LaneId| straight(connector) && reaches_straight(connector);
+            *next.iter()
+                .find(leaves)
+                .or_else(|| next.iter().find(goes_straight))
+                .or_else(|| next.iter().find(straight))
+                .or_else(|| next.iter().find(reaches_straight))
+                .unwrap_or(&next[rng.gen_range(0..next.len())])
+        })
+    }
+
+    /// Seed the world's random source was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The simulation clock.
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// Lane graph vehicles drive on.
+    pub fn network(&self) -> &RoadNetwork {
+        &self.network
+    }
+
+    /// Close calls of the whole session, including those of vehicles that already left.
+    pub fn get_total_close_call_count(&self) -> u32 {
+        self.close_calls.len() as u32
+    }
+
+    /// Every close call of the session, in the order they started.
+    pub fn close_calls(&self) -> &[CloseCall] {
+        &self.close_calls
     }
 
     pub fn min_vehicles_time(&self) -> String {
@@ -117,253 +961,365 @@ impl World {
         format!("{}.{:02}", secs, millis)
     }
 
+    /// Highest average speed of a finished vehicle, in km/h.
     pub fn get_max_velocity(&mut self) -> f64 {
-        self.max_velocity.round()
+        (self.max_velocity * KMH_PER_MS).round()
     }
 
+    /// Lowest average speed of a finished vehicle, in km/h.
     pub fn get_min_velocity(&mut self) -> f64 {
-        self.min_velocity.round()
+        (self.min_velocity * KMH_PER_MS).round()
     }
 
     pub fn get_vehicles_passed(&self) -> u32 {
         self.vehicle_passed
     }
 
-    pub fn spawn_dir(&mut self, dir: u8) {
+    /// Collects the end-of-run statistics shown on the panel.
+    pub fn panel_stats(&self) -> PanelStats {
+        PanelStats {
+            passed_vehicles: self.vehicle_passed,
+            max_velocity: (self.max_velocity * KMH_PER_MS).round(),
+            min_velocity: (self.min_velocity * KMH_PER_MS).round(),
+            max_time: self.max_vehicles_time(),
+            min_time: self.min_vehicles_time(),
+            close_calls: self.get_total_close_call_count(),
+            collisions: self.collisions,
+            conflicts: self.safety.summary(),
+            seed: self.seed,
+            controller: self.controller_name().to_string(),
+            passed_by_approach: Approach::ALL.iter()
+                .map(|a| format!("{} {}", a, self.passed_by_approach.get(a).copied().unwrap_or(0)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            passed_by_movement: Movement::ALL.iter()
+                .map(|m| format!("{} {}", m, self.passed_by_movement.get(m).copied().unwrap_or(0)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            junctions: self.junctions.iter().enumerate()
+                .map(|(id, junction)| junction.summary(id))
+                .collect(),
+            pedestrians: self.pedestrian_summary(),
+            gridlocks: self.gridlock_summary(),
+        }
+    }
+
+    fn play_spawn_sound(&self) {
+        if let Some(spawn_sound) = &self.spawn_sound {
+            sdl2::mixer::Channel::all().play(spawn_sound, 0).unwrap();
+        }
+    }
+
+    /// Movement of the next spawned vehicle, cycling through left, straight and right.
+    ///
+    /// Movements no lane of `approach` allows are skipped; when several lanes allow the movement, the
+    /// vehicle starts on one of them at random. Returns the start lane and the movement.
+    fn next_movement(&mut self, junction: JunctionId, approach: Approach) -> Option<(LaneId, Movement)> {
+        for offset in 0..Movement::ALL.len() {
+            let movement = Movement::ALL[(self.vehicles.len() + offset) % Movement::ALL.len()];
+            let lanes = self.network.inbound_lanes(junction, approach, movement);
+            let start = match lanes.len() {
+                0 => continue,
+                1 => lanes[0],
+                n => lanes[self.rng.gen_range(0..n)],
+            };
+            return Some((start, movement));
+        }
+        None
+    }
+
+    /// Spawns a vehicle entering the map from `approach`, at a random junction on that edge.
+    pub fn spawn_dir(&mut self, approach: Approach) {
         if self.vehicles.len() >= self.max_vehicles {
             return;
         }
 
-        if Instant::now().duration_since(self.last_vehicle_spawn_time) < self.vehicle_spawn_cooldown {
+        if self.clock.now().saturating_sub(self.last_vehicle_spawn_time) < self.vehicle_spawn_cooldown {
             return;
         }
 
-        let lane_width = ROAD_WIDTH / 6;
-
-        let spawn_config = match dir {
-            // From North
-            1 => (
-                380 - ROAD_WIDTH as i32 / 3 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                0,
-                1,
-            ),
-            // From East
-            2 => (
-                800,
-                280 - ROAD_WIDTH as i32 / 3 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                2,
-            ),
-            // From South
-            0 => (
-                420 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                600,
-                0,
-            ),
-            // From West
-            3 => (
-                0,
-                320 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                3,
-            ),
-            _ => unreachable!(),
+        let junctions: Vec<JunctionId> = self.entries.iter()
+            .filter(|&&(_, side)| side == approach)
+            .map(|&(junction, _)| junction)
+            .collect();
+        let junction = match junctions.len() {
+            0 => return,
+            1 => junctions[0],
+            n => junctions[self.rng.gen_range(0..n)],
         };
+        let Some((start, movement)) = self.next_movement(junction, approach) else {
+            return;
+        };
+        let (x, y) = self.network.lane(start).start();
 
         for other in &self.vehicles {
-            if other.x == spawn_config.0.into() && other.y == spawn_config.1.into() {
-                println!("Collision detected at {} {}! Spawn canceled.", spawn_config.0, spawn_config.1);
+            if other.x == x && other.y == y {
+                println!("Collision detected at {} {}! Spawn canceled.", x, y);
                 return;
             }
         }
 
-        let lane = match self.vehicles.len() % 3 {
-            0 => Lane::Middle,
-            1 => Lane::Right,
-            2 => Lane::Left,
-            _ => unreachable!(),
-        };
-
-        self.vehicles.push(Vehicle::new(
-            spawn_config.0,
-            spawn_config.1,
-            spawn_config.2,
-            lane,
-        ));
+        if !self.push_vehicle(start, approach, movement) {
+            return;
+        }
 
         // self.device.resume();
         // std::thread::sleep(Duration::from_millis(100));
         // self.device.pause();
-        sdl2::mixer::Channel::all().play(&self.spawn_sound, 0).unwrap();
+        self.play_spawn_sound();
 
-        self.last_vehicle_spawn_time = Instant::now();
+        self.last_vehicle_spawn_time = self.clock.now();
     }
 
     pub fn auto_spawn(&mut self) {
-        // if Instant::now().duration_since(self.last_vehicle_spawn_time) < self.vehicle_spawn_cooldown
-        //     || self.vehicles.len() >= self.max_vehicles {
-        //     return;
-        // }
         if self.vehicles.len() >= self.max_vehicles {
             return;
         }
 
-        if Instant::now().duration_since(self.last_vehicle_spawn_time) < self.vehicle_spawn_cooldown {
+        if self.clock.now().saturating_sub(self.last_vehicle_spawn_time) < self.vehicle_spawn_cooldown {
             return;
         }
 
-        let mut rng = rand::thread_rng();
-        let direction = rng.gen_range(0..4);
-        let lane_width = ROAD_WIDTH / 6;
-
-        let spawn_config = match direction {
-            // From North
-            1 => (
-                380 - ROAD_WIDTH as i32 / 3 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                0,
-                1,
-            ),
-            // From East
-            2 => (
-                800,
-                280 - ROAD_WIDTH as i32 / 3 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                2,
-            ),
-            // From South
-            0 => (
-                420 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                600,
-                0,
-            ),
-            // From West
-            3 => (
-                0,
-                320 + lane_width as i32 * (self.vehicles.len() % 3) as i32,
-                3,
-            ),
-            _ => unreachable!(),
-        };
-
-        let lane = match self.vehicles.len() % 3 {
-            0 => Lane::Middle,
-            1 => Lane::Right,
-            2 => Lane::Left,
-            _ => unreachable!(),
+        let (junction, approach) = self.entries[self.rng.gen_range(0..self.entries.len())];
+        let Some((start, movement)) = self.next_movement(junction, approach) else {
+            return;
         };
 
-        self.vehicles.push(Vehicle::new(
-            spawn_config.0,
-            spawn_config.1,
-            spawn_config.2,
-            lane
-        ));
+        if !self.push_vehicle(start, approach, movement) {
+            return;
+        }
 
         // self.device.resume();
         // std::thread::sleep(Duration::from_millis(100));
         // self.device.pause();
-        sdl2::mixer::Channel::all().play(&self.spawn_sound, 0).unwrap();
+        self.play_spawn_sound();
 
-        self.last_vehicle_spawn_time = Instant::now();
+        self.last_vehicle_spawn_time = self.clock.now();
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>, texture: &sdl2::render::Texture) -> Result<(), String> {
-        // Draw roads
+    /// Draws the roads, controllers and vehicles, placed on the window by `view`. `sprites` holds the
+    /// sprite of each vehicle class, indexed like `VehicleClass::ALL`.
+    pub fn draw(&self, canvas: &mut Canvas<Window>, sprites: &[sdl2::render::Texture], view: &View) -> Result<(), String> {
+        // Draw roads of every junction first so markings are not covered by a neighbour's road
         canvas.set_draw_color(sdl2::pixels::Color::RGB(100, 100, 100));
-        let lane_width = ROAD_WIDTH / 6;
+        for junction in &self.junctions {
+            let geometry = &junction.geometry;
+            let conflict_box = geometry.conflict_box();
+            canvas.fill_rect(view.rect(conflict_box.left, conflict_box.top, conflict_box.width(), conflict_box.height()))?;
+            for side in Approach::ALL.into_iter().filter(|&side| geometry.has_leg(side)) {
+                // Inbound lanes are on the right of the center line, outbound lanes on the left.
+                let (x, y, width, height) = geometry.road_rect(
+                    side,
+                    -(geometry.outbound_lane_count(side) as f64) * geometry.lane_width,
+                    geometry.lane_count(side) as f64 * geometry.lane_width,
+                );
+                canvas.fill_rect(view.rect(x, y, width, height))?;
+            }
+        }
 
-        // Vertical Road
-        // Left side (downward traffic) - 3 lanes
-        for i in 0..3 {
-            canvas.fill_rect(Rect::new(400 - ROAD_WIDTH as i32 / 2 + (i * lane_width as i32), 0, lane_width, 600))?;
+        // Central islands of roundabouts, inside their circulating lane
+        for junction in &self.junctions {
+            let geometry = &junction.geometry;
+            if geometry.kind == JunctionKind::Roundabout {
+                let (x, y) = view.point(geometry.center.0, geometry.center.1);
+                let radius = view.length(ROUNDABOUT_RADIUS - geometry.lane_width / 2.0);
+                canvas.filled_circle(x as i16, y as i16, radius as i16, sdl2::pixels::Color::RGB(60, 120, 60))?;
+            }
         }
 
-        // Right side (upward traffic) - 3 lanes
-        for i in 0..3 {
-            canvas.fill_rect(Rect::new(400 + (i * lane_width as i32), 0, lane_width, 600))?;
+        // Draw lane markings, stopping at the intersection box
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+        for junction in &self.junctions {
+            let geometry = &junction.geometry;
+            let half_marking = LANE_MARKING_WIDTH / 2.0;
+            for side in Approach::ALL.into_iter().filter(|&side| geometry.has_leg(side)) {
+                let outbound_lanes = geometry.outbound_lane_count(side);
+                for i in 1..outbound_lanes + geometry.lane_count(side) {
+                    let offset = (i as f64 - outbound_lanes as f64) * geometry.lane_width;
+                    let (x, y, width, height) = geometry.road_rect(side, offset - half_marking, offset + half_marking);
+                    canvas.fill_rect(view.rect(x, y, width, height))?;
+                }
+
+                // Draw the stop line across the lanes entering the intersection
+                let (x, y, width, height) = geometry.stop_line(side);
+                canvas.fill_rect(view.rect(x, y, width, height))?;
+            }
         }
 
-        // Horizontal Road
-        // Top side (leftward traffic) - 3 lanes
-        for i in 0..3 {
-            canvas.fill_rect(Rect::new(0, 300 - ROAD_WIDTH as i32 / 2 + (i * lane_width as i32), 800, lane_width))?;
+        // Draw crosswalks next to the stop lines, striped along the road
+        for (_, crosswalk) in &self.crosswalks {
+            let area = crosswalk.area;
+            let vertical_road = matches!(crosswalk.side, Approach::North | Approach::South);
+            let across = if vertical_road { area.width() } else { area.height() };
+            let stripes = (across / (2.0 * CROSSWALK_STRIPE_WIDTH)).round() as usize;
+            for stripe in 0..stripes {
+                let offset = (2 * stripe) as f64 * CROSSWALK_STRIPE_WIDTH + CROSSWALK_STRIPE_WIDTH / 2.0;
+                let rect = if vertical_road {
+                    view.rect(area.left + offset, area.top, CROSSWALK_STRIPE_WIDTH, area.height())
+                } else {
+                    view.rect(area.left, area.top + offset, area.width(), CROSSWALK_STRIPE_WIDTH)
+                };
+                canvas.fill_rect(rect)?;
+            }
         }
 
-        // Bottom side (rightward traffic) - 3 lanes
-        for i in 0..3 {
-            canvas.fill_rect(Rect::new(0, 300 + (i * lane_width as i32), 800, lane_width))?;
+        for junction in &self.junctions {
+            junction.controller.draw(canvas, view)?;
         }
 
-        // Draw lane markings
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+        // Draw vehicles
+        for vehicle in &self.vehicles {
+            vehicle.draw(canvas, &sprites[vehicle.class.index()], view)?;
+        }
 
-        // Vertical lane markings
-        for i in 1..6 {
-            let x = 400 - ROAD_WIDTH as i32 / 2 + (i * lane_width as i32);
-            canvas.fill_rect(Rect::new(x - 2, 0, 4, 300 - ROAD_WIDTH as u32 / 2))?;
-            canvas.fill_rect(Rect::new(x - 2, 300 + ROAD_WIDTH as i32 / 2, 4, 300 - ROAD_WIDTH as u32 / 2))?;
+        for pedestrian in &self.pedestrians {
+            pedestrian.draw(canvas, view)?;
         }
 
-        // Horizontal lane markings
-        for i in 1..6 {
-            let y = 300 - ROAD_WIDTH as i32 / 2 + (i * lane_width as i32);
-            canvas.fill_rect(Rect::new(0, y - 2, 400 - ROAD_WIDTH as u32 / 2, 4))?;
-            canvas.fill_rect(Rect::new(400 + ROAD_WIDTH as i32 / 2, y - 2, 400 - ROAD_WIDTH as u32 / 2, 4))?;
+        // Outline the pair that halted the simulation
+        if let Some((first, second)) = self.halted_on {
+            for vehicle in self.vehicles.iter().filter(|v| v.id == first || v.id == second) {
+                let corners = vehicle.footprint().inflated(vehicle.width() / 4.0).corners().map(|(x, y)| view.point(x, y));
+                let xs = corners.map(|(x, _)| x as i16);
+                let ys = corners.map(|(_, y)| y as i16);
+                canvas.polygon(&xs, &ys, COLLISION_HIGHLIGHT)?;
+            }
         }
 
-        // Draw horizontal connecting lines at lane ends
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+        Ok(())
+    }
+}
 
-        // North end horizontal connectors
-        canvas.fill_rect(Rect::new(
-            280,                          // Start from rightmost lane
-            176,                          // Some distance from the bottom
-            ROAD_WIDTH as u32 / 2,        // Span across right lanes
-            4                             // Line thickness
-        ))?;
-
-        // South end horizontal connectors
-        canvas.fill_rect(Rect::new(
-            400,                          // Start from rightmost lane
-            420,                          // Some distance from the bottom
-            ROAD_WIDTH as u32 / 2,        // Span across right lanes
-            4                             // Line thickness
-        ))?;
-
-        // East end horizontal connectors
-        canvas.fill_rect(Rect::new(
-            520,                          // Some distance from right edge
-            180,                          // Start from rightmost lane
-            4,                            // Line thickness
-            ROAD_WIDTH as u32 / 2         // Span across right lanes
-        ))?;
-
-        // West end horizontal connectors
-        canvas.fill_rect(Rect::new(
-            277,                          // Some distance from left edge
-            300,                          // Start from rightmost lane
-            4,                            // Line thickness
-            ROAD_WIDTH as u32 / 2         // Span across right lanes
-        ))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::road::LaneLayout;
+    use crate::vehicle_class::VehicleMix;
 
-        // Draw vehicles
-        for vehicle in &self.vehicles {
-            vehicle.draw(canvas, texture)?;
+    /// Plans `per_start` routes from every lane entering the map, for each movement it allows, and
+    /// checks that each one ends on a lane leaving the map.
+    fn assert_routes_leave_the_map(scenario: Scenario, per_start: usize) {
+        scenario.validate().unwrap();
+        let mut world = World::headless(1, scenario);
+        let network = Rc::clone(&world.network);
+        let entered: HashSet<LaneId> = network.lanes().iter().flat_map(|lane| lane.next.iter().copied()).collect();
+        let starts: Vec<LaneId> = network.lanes().iter()
+            .filter(|lane| matches!(lane.kind, LaneKind::Inbound { .. }) && !entered.contains(&lane.id))
+            .map(|lane| lane.id)
+            .collect();
+        let mut planned = 0;
+        for &start in &starts {
+            for movement in [Movement::Left, Movement::Straight, Movement::Right] {
+                let allows = network.lane(start).next.iter().any(|&next| {
+                    matches!(network.lane(next).kind, LaneKind::Connector { movement: m, .. } if m == movement)
+                });
+                for _ in 0..per_start * usize::from(allows) {
+                    let route = world.plan_route(start, movement).unwrap();
+                    let last = network.lane(*route.last().unwrap());
+                    assert!(matches!(last.kind, LaneKind::Outbound { .. }), "route {:?} ends inside the map", route);
+                    planned += 1;
+                }
+            }
         }
+        assert!(planned > 0);
+    }
 
-        Ok(())
+    fn grid(text: &str, kind: JunctionKind, lanes: LaneLayout) -> Scenario {
+        Scenario { kind, lanes, ..Scenario::parse(text).unwrap() }
     }
 
-    fn spawn_vehicle(&mut self, direction: u8) {
-        let (x, y, _angle) = match direction {
-            0 => (420, 580, 0.0), // Bottom to top
-            1 => (380, 20, 0.0),    // Top to bottom
-            2 => (20, 320, 0.0),   // Left to right
-            3 => (780, 280, 0.0), // Right to left
-            _ => unreachable!(),
+    #[test]
+    fn routes_on_roundabout_grids_leave_the_map() {
+        assert_routes_leave_the_map(grid("grid:2x2", JunctionKind::Roundabout, LaneLayout::single()), 200);
+        assert_routes_leave_the_map(grid("grid:3x3", JunctionKind::Roundabout, LaneLayout::single()), 100);
+    }
+
+    #[test]
+    fn routes_on_cross_grids_leave_the_map() {
+        assert_routes_leave_the_map(grid("grid:2x2", JunctionKind::Cross, LaneLayout::default()), 100);
+        assert_routes_leave_the_map(grid("corridor:4", JunctionKind::Cross, LaneLayout::default()), 100);
+    }
+
+    #[test]
+    fn routes_on_grids_with_shared_lanes_leave_the_map() {
+        for lanes in ["N:LS,R E:LS,R S:LS,R W:LS,R", "N:L,SR E:L,SR S:L,SR W:L,SR", "N:LSR E:LSR S:LSR W:LSR"] {
+            let lanes = LaneLayout::parse(lanes).unwrap();
+            assert_routes_leave_the_map(grid("grid:2x2", JunctionKind::Cross, lanes.clone()), 100);
+            assert_routes_leave_the_map(grid("grid:3x2", JunctionKind::Cross, lanes), 50);
+        }
+    }
+
+    #[test]
+    fn grid_neighbours_and_pedestrians_match_a_scan_of_everyone() {
+        let scenario = Scenario {
+            vehicle_mix: VehicleMix::parse("car:70,truck:10,bus:5,motorcycle:15").unwrap(),
+            ..Scenario::parse("grid:2x2").unwrap()
         };
+        let mut world = World::headless(7, scenario);
+        world.set_pedestrian_demand(60.0);
+        let mut near_pairs = 0;
+        for _ in 0..3600 {
+            world.auto_spawn();
+            world.auto_spawn_pedestrians();
 
-        let route = rand::thread_rng().gen_range(0..3);
-        println!("Generated Vehicle On Route: {}", route);
-        //let route = 1;
-        self.vehicles
-            .push(Vehicle::new(x, y, direction, Lane::Middle));
+            // Each vehicle moves the same whether it sees its grid neighbours or every other vehicle.
+            let grid = SpatialGrid::new(NEIGHBOUR_CELL_SIZE, world.vehicles.iter().map(|v| (v.x, v.y)));
+            for (i, vehicle) in world.vehicles.iter().enumerate() {
+                let neighbours: Vec<&Vehicle> = grid.query((vehicle.x, vehicle.y), vehicle.interaction_radius())
+                    .into_iter()
+                    .filter(|&j| j != i)
+                    .map(|j| &world.vehicles[j])
+                    .collect();
+                let everyone: Vec<&Vehicle> = world.vehicles.iter().filter(|other| other.id != vehicle.id).collect();
+                let (mut near, mut all) = (vehicle.clone(), vehicle.clone());
+                near.update(&neighbours, Command::Proceed, &world.clock);
+                all.update(&everyone, Command::Proceed, &world.clock);
+                assert_eq!(
+                    (near.x, near.y, near.speed, near.waiting_for),
+                    (all.x, all.y, all.speed, all.waiting_for),
+                    "vehicle {} at tick {}", vehicle.id, world.clock.tick(),
+                );
+            }
+
+            // Vehicles yield to pedestrians, so they seldom come within the conflict distance; a wider
+            // distance checks the grid finds the pairs a scan does.
+            for distance in [PEDESTRIAN_CONFLICT_DISTANCE, 5.0] {
+                let scanned: HashSet<(u32, u32)> = world.pedestrians.iter()
+                    .filter(|pedestrian| pedestrian.is_crossing())
+                    .flat_map(|pedestrian| {
+                        world.vehicles.iter()
+                            .filter(|vehicle| vehicle.is_near(pedestrian.x, pedestrian.y, distance))
+                            .map(|vehicle| (vehicle.id, pedestrian.id))
+                    })
+                    .collect();
+                assert_eq!(world.pedestrians_near_vehicles(distance), scanned, "tick {}", world.clock.tick());
+                near_pairs += scanned.len();
+            }
+
+            world.update();
+        }
+        assert!(near_pairs > 0);
+    }
+
+    #[test]
+    fn headless_runs_with_the_same_seed_are_identical() {
+        let run = || {
+            let scenario = Scenario {
+                kind: JunctionKind::Roundabout,
+                vehicle_mix: VehicleMix::parse("car:70,truck:10,bus:5,motorcycle:15").unwrap(),
+                ..Scenario::parse("grid:2x2").unwrap()
+            };
+            let mut world = World::headless(3, scenario);
+            world.set_pedestrian_demand(60.0);
+            for _ in 0..3600 {
+                world.auto_spawn();
+                world.auto_spawn_pedestrians();
+                world.update();
+            }
+            (world.panel_stats(), world.events)
+        };
+        let (stats, events) = run();
+        assert!(stats.passed_vehicles > 0);
+        assert_eq!(run(), (stats, events));
     }
 }